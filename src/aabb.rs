@@ -0,0 +1,117 @@
+use crate::ray::Ray;
+use crate::vector3::Vector3;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3, max: Vector3) -> Self {
+        Aabb { min, max }
+    }
+
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(&other.min),
+            max: self.max.max(&other.max),
+        }
+    }
+
+    pub fn centroid(&self) -> Vector3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let extent = self.max - self.min;
+        2. * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let extent = self.max - self.min;
+        if extent.x > extent.y && extent.x > extent.z {
+            0
+        } else if extent.y > extent.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+
+        for axis in 0..3 {
+            let inverse_direction = 1. / ray.direction[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inverse_direction;
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inverse_direction;
+            if inverse_direction < 0. {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = t0.max(t_min);
+            t_max = t1.min(t_max);
+            if t_max < t_min {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Vector3::from((-1., -1., -1.)), Vector3::from((1., 1., 1.)))
+    }
+
+    #[test]
+    fn is_hit_by_ray_going_through() {
+        let ray = Ray::new(Vector3::from((0., 0., 5.)), Vector3::from((0., 0., -1.)));
+
+        assert!(unit_box().hit(&ray, 0., 10.));
+    }
+
+    #[test]
+    fn is_not_hit_by_ray_going_aside() {
+        let ray = Ray::new(Vector3::from((2., 0., 5.)), Vector3::from((0., 0., -1.)));
+
+        assert!(!unit_box().hit(&ray, 0., 10.));
+    }
+
+    #[test]
+    fn is_not_hit_outside_of_range() {
+        let ray = Ray::new(Vector3::from((0., 0., 5.)), Vector3::from((0., 0., -1.)));
+
+        assert!(!unit_box().hit(&ray, 0., 3.));
+        assert!(!unit_box().hit(&ray, 7., 10.));
+    }
+
+    #[test]
+    fn can_surround_another_box() {
+        let other = Aabb::new(Vector3::from((0., 0., 0.)), Vector3::from((3., 2., 1.)));
+
+        let surrounding = unit_box().surrounding(&other);
+
+        assert_eq!(Vector3::from((-1., -1., -1.)), surrounding.min);
+        assert_eq!(Vector3::from((3., 2., 1.)), surrounding.max);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn gives_surface_area() {
+        assert_eq!(24., unit_box().surface_area());
+    }
+
+    #[test]
+    fn gives_longest_axis() {
+        let aabb = Aabb::new(Vector3::from((0., 0., 0.)), Vector3::from((1., 3., 2.)));
+
+        assert_eq!(1, aabb.longest_axis());
+    }
+}
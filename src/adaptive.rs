@@ -0,0 +1,157 @@
+use crate::radiance::Radiance;
+
+/// Added to the mean luminance of a pixel before computing its relative error,
+/// so that nearly black pixels do not use up the whole sample budget.
+const DARKNESS_OFFSET: f64 = 0.01;
+
+/// Running mean of the samples of a pixel, and variance of their luminance
+/// (Welford's algorithm).
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct PixelStatistics {
+    pub count: u32,
+    pub(crate) sum: Radiance,
+    pub(crate) mean_luminance: f64,
+    pub(crate) squared_deviations: f64,
+}
+
+impl PixelStatistics {
+    pub fn add(&mut self, radiance: Radiance) {
+        self.count += 1;
+        self.sum += radiance;
+
+        let luminance = radiance.luminance();
+        let deviation = luminance - self.mean_luminance;
+        self.mean_luminance += deviation / self.count as f64;
+        self.squared_deviations += deviation * (luminance - self.mean_luminance);
+    }
+
+    pub fn mean(&self) -> Radiance {
+        if self.count == 0 {
+            Radiance::default()
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+
+    /// Unbiased variance of the luminance of the samples.
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.
+        } else {
+            self.squared_deviations / (self.count - 1) as f64
+        }
+    }
+
+    /// Standard error of the mean luminance, relative to it.
+    pub fn relative_error(&self) -> f64 {
+        if self.count == 0 {
+            return f64::INFINITY;
+        }
+        let standard_error = (self.variance() / self.count as f64).sqrt();
+        standard_error / (self.mean_luminance.max(0.) + DARKNESS_OFFSET)
+    }
+}
+
+/// Keeps sampling a pixel until the relative error of its mean falls under
+/// `threshold`, checking it every `min_samples` samples, or until `max_samples`
+/// are taken.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AdaptiveSampling {
+    pub min_samples: u32,
+    pub max_samples: u32,
+    pub threshold: f64,
+}
+
+impl AdaptiveSampling {
+    /// Always takes `sample_count` samples.
+    pub fn fixed(sample_count: u32) -> Self {
+        AdaptiveSampling {
+            min_samples: sample_count,
+            max_samples: sample_count,
+            threshold: 0.,
+        }
+    }
+
+    pub fn is_done(&self, statistics: &PixelStatistics) -> bool {
+        let count = statistics.count;
+        let min_samples = self.min_samples.clamp(1, self.max_samples.max(1));
+
+        count >= self.max_samples
+            || (count >= min_samples
+                && count.is_multiple_of(min_samples)
+                && statistics.relative_error() <= self.threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grey(value: f64) -> Radiance {
+        Radiance::new(value, value, value)
+    }
+
+    fn get_statistics(values: &[f64]) -> PixelStatistics {
+        let mut statistics = PixelStatistics::default();
+        for value in values {
+            statistics.add(grey(*value));
+        }
+        statistics
+    }
+
+    #[test]
+    fn tracks_mean_and_variance() {
+        let statistics = get_statistics(&[1., 2., 3., 4.]);
+
+        assert_eq!(4, statistics.count);
+        assert_eq!(grey(2.5), statistics.mean());
+        assert!((statistics.variance() - 5. / 3.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn constant_samples_have_no_error() {
+        let statistics = get_statistics(&[0.5; 8]);
+
+        assert_eq!(0., statistics.relative_error());
+    }
+
+    #[test]
+    fn error_is_relative_to_the_mean() {
+        let dim = get_statistics(&[0.9, 1.1, 0.9, 1.1]);
+        let bright = get_statistics(&[9.9, 10.1, 9.9, 10.1]);
+
+        assert!(bright.relative_error() < dim.relative_error() / 5.);
+    }
+
+    #[test]
+    fn empty_statistics_are_black_and_unknown() {
+        let statistics = PixelStatistics::default();
+
+        assert_eq!(Radiance::default(), statistics.mean());
+        assert_eq!(f64::INFINITY, statistics.relative_error());
+    }
+
+    #[test]
+    fn fixed_sampling_stops_at_the_sample_count() {
+        let sampling = AdaptiveSampling::fixed(4);
+
+        assert!(!sampling.is_done(&get_statistics(&[0.5; 3])));
+        assert!(sampling.is_done(&get_statistics(&[0.5; 4])));
+    }
+
+    #[test]
+    fn adaptive_sampling_stops_on_converged_pixels() {
+        let sampling = AdaptiveSampling {
+            min_samples: 4,
+            max_samples: 64,
+            threshold: 0.05,
+        };
+        let noisy = [0., 2., 0., 2., 0., 2., 0., 2.];
+
+        assert!(!sampling.is_done(&get_statistics(&[0.5; 3])));
+        assert!(!sampling.is_done(&get_statistics(&[0.5; 5])));
+        assert!(sampling.is_done(&get_statistics(&[0.5; 4])));
+        assert!(!sampling.is_done(&get_statistics(&noisy)));
+        assert!(sampling.is_done(&get_statistics(&[2.; 64])));
+    }
+}
@@ -0,0 +1,272 @@
+use crate::aabb::Aabb;
+use crate::hit::{closest_hit, HitRecord, Hittable, HittableList};
+use crate::ray::Ray;
+use rand::RngCore;
+
+const MAX_OBJECTS_IN_LEAF: usize = 4;
+const TRAVERSAL_COST: f64 = 0.125;
+
+enum BvhContent {
+    Leaf(Vec<Box<dyn Hittable>>),
+    Branch(Box<BvhNode>, Box<BvhNode>),
+}
+
+pub struct BvhNode {
+    bounding_box: Aabb,
+    content: BvhContent,
+}
+
+struct BoundedObject {
+    object: Box<dyn Hittable>,
+    bounding_box: Aabb,
+}
+
+impl BvhNode {
+    /// Builds the hierarchy with a surface area heuristic split on the longest
+    /// axis of the centroids. Every object must have a bounding box.
+    pub fn new(list: HittableList) -> Self {
+        let objects: Vec<BoundedObject> = list
+            .into_objects()
+            .into_iter()
+            .map(|object| {
+                let bounding_box = object
+                    .bounding_box()
+                    .expect("Objects in a BVH must have a bounding box");
+                BoundedObject {
+                    object,
+                    bounding_box,
+                }
+            })
+            .collect();
+
+        assert!(!objects.is_empty(), "Cannot build a BVH without objects");
+
+        BvhNode::build(objects)
+    }
+
+    fn build(mut objects: Vec<BoundedObject>) -> Self {
+        let bounding_box = surrounding_box(&objects);
+
+        if objects.len() == 1 {
+            return BvhNode::leaf(bounding_box, objects);
+        }
+
+        let axis = centroid_box(&objects).longest_axis();
+
+        objects.sort_by(|a, b| {
+            let a = a.bounding_box.centroid()[axis];
+            let b = b.bounding_box.centroid()[axis];
+            a.partial_cmp(&b).unwrap()
+        });
+
+        let (split_index, split_cost) = best_split(&objects, &bounding_box);
+        let leaf_cost = objects.len() as f64;
+
+        if objects.len() <= MAX_OBJECTS_IN_LEAF && leaf_cost <= split_cost {
+            return BvhNode::leaf(bounding_box, objects);
+        }
+
+        let right_objects = objects.split_off(split_index);
+        BvhNode {
+            bounding_box,
+            content: BvhContent::Branch(
+                Box::new(BvhNode::build(objects)),
+                Box::new(BvhNode::build(right_objects)),
+            ),
+        }
+    }
+
+    fn leaf(bounding_box: Aabb, objects: Vec<BoundedObject>) -> Self {
+        BvhNode {
+            bounding_box,
+            content: BvhContent::Leaf(objects.into_iter().map(|b| b.object).collect()),
+        }
+    }
+}
+
+fn surrounding_box(objects: &[BoundedObject]) -> Aabb {
+    objects
+        .iter()
+        .skip(1)
+        .fold(objects[0].bounding_box, |surrounding, bounded| {
+            surrounding.surrounding(&bounded.bounding_box)
+        })
+}
+
+fn centroid_box(objects: &[BoundedObject]) -> Aabb {
+    let first_centroid = objects[0].bounding_box.centroid();
+    objects.iter().skip(1).fold(
+        Aabb::new(first_centroid, first_centroid),
+        |surrounding, bounded| {
+            let centroid = bounded.bounding_box.centroid();
+            surrounding.surrounding(&Aabb::new(centroid, centroid))
+        },
+    )
+}
+
+/// Returns the index splitting the sorted objects in two with the lowest
+/// estimated cost, along with that cost, relative to intersecting one object.
+fn best_split(objects: &[BoundedObject], bounding_box: &Aabb) -> (usize, f64) {
+    let count = objects.len();
+
+    let mut right_areas = vec![0.; count];
+    let mut right_box = objects[count - 1].bounding_box;
+    for i in (1..count).rev() {
+        right_box = right_box.surrounding(&objects[i].bounding_box);
+        right_areas[i] = right_box.surface_area();
+    }
+
+    let total_area = bounding_box.surface_area();
+    let mut left_box = objects[0].bounding_box;
+    let mut best = (count / 2, f64::MAX);
+    for i in 1..count {
+        left_box = left_box.surrounding(&objects[i - 1].bounding_box);
+        let cost = TRAVERSAL_COST
+            + (i as f64 * left_box.surface_area() + (count - i) as f64 * right_areas[i])
+                / total_area;
+        if cost < best.1 {
+            best = (i, cost);
+        }
+    }
+
+    best
+}
+
+impl BvhNode {
+    /// Closest of the hits found by `hit_object` on the objects whose bounding
+    /// box `ray` crosses.
+    fn closest_hit<'a>(
+        &'a self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        hit_object: &mut dyn FnMut(&'a dyn Hittable, f64) -> Option<HitRecord<'a>>,
+    ) -> Option<HitRecord<'a>> {
+        if !self.bounding_box.hit(ray, t_min, t_max) {
+            return None;
+        }
+
+        match &self.content {
+            BvhContent::Leaf(objects) => closest_hit(objects, t_max, hit_object),
+            BvhContent::Branch(left, right) => {
+                let left_hit = left.closest_hit(ray, t_min, t_max, hit_object);
+                let closest_t = left_hit.as_ref().map_or(t_max, |hit| hit.t);
+                right
+                    .closest_hit(ray, t_min, closest_t, hit_object)
+                    .or(left_hit)
+            }
+        }
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.closest_hit(ray, t_min, t_max, &mut |object, closest_t| {
+            object.hit(ray, t_min, closest_t)
+        })
+    }
+
+    fn sample_hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        self.closest_hit(ray, t_min, t_max, &mut |object, closest_t| {
+            object.sample_hit(ray, t_min, closest_t, rng)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounding_box)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hit::{MovingSphere, Sphere};
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+    use crate::vector3::Vector3;
+
+    fn get_sphere(center: (f64, f64, f64)) -> Box<dyn Hittable> {
+        Box::new(Sphere::new(
+            Vector3::from(center),
+            0.5,
+            Box::new(Lambertian::new(ConstantTexture::new(Vector3::default()))),
+        ))
+    }
+
+    fn get_row_of_spheres() -> HittableList {
+        HittableList::new((0..20).map(|i| get_sphere((i as f64, 0., 0.))).collect())
+    }
+
+    #[test]
+    fn surrounds_all_objects() {
+        let bvh = BvhNode::new(get_row_of_spheres());
+
+        let bounding_box = bvh.bounding_box().unwrap();
+
+        assert_eq!(Vector3::from((-0.5, -0.5, -0.5)), bounding_box.min);
+        assert_eq!(Vector3::from((19.5, 0.5, 0.5)), bounding_box.max);
+    }
+
+    #[test]
+    fn hits_the_same_objects_as_the_list() {
+        let list = get_row_of_spheres();
+        let bvh = BvhNode::new(get_row_of_spheres());
+
+        for i in 0..40 {
+            let ray = Ray::new(
+                Vector3::from((i as f64 * 0.5, 0.2, 5.)),
+                Vector3::from((0., 0., -1.)),
+            );
+
+            let list_hit = list.hit(&ray, 0., f64::MAX).map(|hit| hit.point);
+            let bvh_hit = bvh.hit(&ray, 0., f64::MAX).map(|hit| hit.point);
+
+            assert_eq!(list_hit, bvh_hit);
+        }
+    }
+
+    #[test]
+    fn gives_closest_hit_along_the_ray() {
+        let bvh = BvhNode::new(get_row_of_spheres());
+        let ray = Ray::new(Vector3::from((-5., 0., 0.)), Vector3::from((1., 0., 0.)));
+
+        let hit = bvh.hit(&ray, 0., f64::MAX).unwrap();
+
+        assert_eq!(Vector3::from((-0.5, 0., 0.)), hit.point);
+    }
+
+    #[test]
+    fn can_be_built_from_a_single_object() {
+        let bvh = BvhNode::new(HittableList::new(vec![get_sphere((0., 0., 0.))]));
+        let ray = Ray::new(Vector3::from((0., 0., 5.)), Vector3::from((0., 0., -1.)));
+
+        assert!(bvh.hit(&ray, 0., f64::MAX).is_some());
+    }
+
+    #[test]
+    fn finds_moving_spheres_outside_of_their_motion_interval() {
+        let sphere = MovingSphere::new(
+            (Vector3::from((0., 0., 0.)), 1.),
+            (Vector3::from((2., 0., 0.)), 2.),
+            0.5,
+            Box::new(Lambertian::new(ConstantTexture::new(Vector3::default()))),
+        );
+        let bvh = BvhNode::new(HittableList::new(vec![
+            Box::new(sphere),
+            get_sphere((10., 0., 0.)),
+        ]));
+        let direction = Vector3::from((0., 0., -1.));
+
+        let before = Ray::with_time(Vector3::from((0., 0., 5.)), direction, 0.);
+        let after = Ray::with_time(Vector3::from((2., 0., 5.)), direction, 3.);
+
+        assert!(bvh.hit(&before, 0., f64::MAX).is_some());
+        assert!(bvh.hit(&after, 0., f64::MAX).is_some());
+    }
+}
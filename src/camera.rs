@@ -1,11 +1,11 @@
 use crate::ray::Ray;
-use crate::vector3::{random_in_unit_sphere, Vector3};
+use crate::vector3::{random_in_unit_disk, Vector3};
+use rand::{Rng, RngCore};
 use std::f64;
 
 pub struct Basis {
     u: Vector3,
     v: Vector3,
-    w: Vector3,
 }
 
 pub struct Camera {
@@ -15,6 +15,8 @@ pub struct Camera {
     origin: Vector3,
     orthonormal_basis: Basis,
     lens_radius: f64,
+    shutter_open: f64,
+    shutter_close: f64,
 }
 
 type Degrees = f64;
@@ -47,19 +49,82 @@ impl Camera {
             horizontal: u * (2. * half_width * focus_distance),
             vertical: v * (2. * half_height * focus_distance),
             origin,
-            orthonormal_basis: Basis { u, v, w },
+            orthonormal_basis: Basis { u, v },
             lens_radius: aperture / 2.,
+            shutter_open: 0.,
+            shutter_close: 0.,
         }
     }
-    pub fn get_ray(&self, u: f64, v: f64) -> Ray {
-        let random_dispersion = random_in_unit_sphere() * self.lens_radius;
+
+    pub fn origin(&self) -> Vector3 {
+        self.origin
+    }
+
+    /// Rays are emitted at random times between the opening and the closing of
+    /// the shutter, which blurs moving objects.
+    pub fn with_shutter(self, shutter_open: f64, shutter_close: f64) -> Self {
+        assert!(shutter_open <= shutter_close);
+
+        Camera {
+            shutter_open,
+            shutter_close,
+            ..self
+        }
+    }
+
+    pub fn get_ray(&self, u: f64, v: f64, rng: &mut dyn RngCore) -> Ray {
+        let random_dispersion = random_in_unit_disk(rng) * self.lens_radius;
         let offset = self.orthonormal_basis.u * random_dispersion.x
             + self.orthonormal_basis.v * random_dispersion.y;
-        Ray::new(
+        let time = if self.shutter_open < self.shutter_close {
+            rng.gen_range(self.shutter_open, self.shutter_close)
+        } else {
+            self.shutter_open
+        };
+        Ray::with_time(
             self.origin + offset,
             (self.lower_left_corner + self.horizontal * u + self.vertical * v)
                 - self.origin
                 - offset,
+            time,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Pcg32;
+
+    fn get_camera() -> Camera {
+        Camera::new(
+            Vector3::default(),
+            Vector3::from((0., 0., -1.)),
+            Vector3::from((0., 1., 0.)),
+            90.,
+            1.,
+            0.,
+            1.,
         )
     }
+
+    #[test]
+    fn shoots_rays_at_shutter_opening_by_default() {
+        let mut rng = Pcg32::new(0, 0);
+        let ray = get_camera().get_ray(0.5, 0.5, &mut rng);
+
+        assert_eq!(0., ray.time);
+        assert_eq!(Vector3::from((0., 0., -1.)), ray.direction);
+    }
+
+    #[test]
+    fn samples_times_within_the_shutter_interval() {
+        let mut rng = Pcg32::new(0, 0);
+        let camera = get_camera().with_shutter(1., 2.);
+
+        for _ in 0..100 {
+            let time = camera.get_ray(0.5, 0.5, &mut rng).time;
+            assert!((1. ..2.).contains(&time));
+        }
+    }
 }
@@ -0,0 +1,237 @@
+use crate::adaptive::PixelStatistics;
+use crate::radiance::Radiance;
+use crate::render::Framebuffer;
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"WRTCHECK";
+const VERSION: u32 = 1;
+const PIXEL_SIZE: usize = 4 + 5 * 8;
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Parse(String),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointError::Io(error) => write!(f, "{}", error),
+            CheckpointError::Parse(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(error: io::Error) -> Self {
+        CheckpointError::Io(error)
+    }
+}
+
+/// Everything needed to continue a render: the statistics of its pixels and
+/// its seed. Given the `settings` of the scene and the renderer, which must not
+/// change before the render is continued, the random numbers of a sample only
+/// depend on the seed, the pixel and the index of the sample. The count of
+/// samples of each pixel then stands for the state of its generator.
+#[derive(Debug, PartialEq)]
+pub struct Checkpoint {
+    pub settings: String,
+    pub seed: u64,
+    pub statistics: Framebuffer<PixelStatistics>,
+}
+
+fn write_pixel(content: &mut Vec<u8>, pixel: &PixelStatistics) {
+    content.extend_from_slice(&pixel.count.to_le_bytes());
+    for value in &[
+        pixel.sum.r,
+        pixel.sum.g,
+        pixel.sum.b,
+        pixel.mean_luminance,
+        pixel.squared_deviations,
+    ] {
+        content.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn read_pixel(data: &[u8]) -> PixelStatistics {
+    let value = |index: usize| {
+        let start = 4 + index * 8;
+        f64::from_le_bytes(data[start..start + 8].try_into().unwrap())
+    };
+
+    PixelStatistics {
+        count: u32::from_le_bytes(data[..4].try_into().unwrap()),
+        sum: Radiance::new(value(0), value(1), value(2)),
+        mean_luminance: value(3),
+        squared_deviations: value(4),
+    }
+}
+
+/// Reads the fields of a checkpoint one after the other.
+struct Reader<'a> {
+    content: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], CheckpointError> {
+        if self.content.len() < length {
+            return Err(CheckpointError::Parse(String::from("truncated checkpoint")));
+        }
+        let (bytes, rest) = self.content.split_at(length);
+        self.content = rest;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, CheckpointError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, CheckpointError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+}
+
+impl Checkpoint {
+    pub fn get_file_content(&self) -> Vec<u8> {
+        let (width, height) = (self.statistics.width, self.statistics.height);
+        let mut content = Vec::with_capacity(
+            MAGIC.len() + 24 + self.settings.len() + (width * height) as usize * PIXEL_SIZE,
+        );
+        content.extend_from_slice(MAGIC);
+        content.extend_from_slice(&VERSION.to_le_bytes());
+        content.extend_from_slice(&self.seed.to_le_bytes());
+        content.extend_from_slice(&(self.settings.len() as u32).to_le_bytes());
+        content.extend_from_slice(self.settings.as_bytes());
+        content.extend_from_slice(&width.to_le_bytes());
+        content.extend_from_slice(&height.to_le_bytes());
+
+        for y in 0..height {
+            for x in 0..width {
+                write_pixel(&mut content, &self.statistics.get(x, y));
+            }
+        }
+        content
+    }
+
+    pub fn parse(content: &[u8]) -> Result<Self, CheckpointError> {
+        let mut reader = Reader { content };
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(CheckpointError::Parse(String::from("not a checkpoint")));
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(CheckpointError::Parse(format!(
+                "unsupported checkpoint version {}",
+                version
+            )));
+        }
+        let seed = reader.u64()?;
+        let settings_length = reader.u32()? as usize;
+        let settings = String::from_utf8(reader.bytes(settings_length)?.to_vec())
+            .map_err(|_| CheckpointError::Parse(String::from("invalid settings")))?;
+        let width = reader.u32()?;
+        let height = reader.u32()?;
+        if width == 0 || height == 0 {
+            return Err(CheckpointError::Parse(String::from("empty image")));
+        }
+
+        // The whole pixel data is read before allocating the image, whose
+        // size comes from a header which may be corrupt.
+        let data_size = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixel_count| pixel_count.checked_mul(PIXEL_SIZE))
+            .ok_or_else(|| CheckpointError::Parse(String::from("truncated checkpoint")))?;
+        let mut pixels = reader.bytes(data_size)?.chunks(PIXEL_SIZE);
+
+        let mut statistics = Framebuffer::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                statistics.set(x, y, read_pixel(pixels.next().unwrap()));
+            }
+        }
+
+        Ok(Checkpoint {
+            settings,
+            seed,
+            statistics,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, CheckpointError> {
+        Checkpoint::parse(&fs::read(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_checkpoint() -> Checkpoint {
+        let mut statistics = Framebuffer::new(3, 2);
+        let mut pixel = PixelStatistics::default();
+        pixel.add(Radiance::new(0.1, 0.2, 0.3));
+        pixel.add(Radiance::new(1.5, 0., 2.));
+        statistics.set(2, 1, pixel);
+
+        Checkpoint {
+            settings: String::from("width = 3\nheight = 2\n"),
+            seed: 1234,
+            statistics,
+        }
+    }
+
+    #[test]
+    fn round_trips_through_its_file_content() {
+        let checkpoint = get_checkpoint();
+
+        let parsed = Checkpoint::parse(&checkpoint.get_file_content()).unwrap();
+
+        assert_eq!(checkpoint, parsed);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let error = Checkpoint::parse(b"P6\n1 1\n255\n").unwrap_err();
+
+        assert_eq!("not a checkpoint", error.to_string());
+    }
+
+    #[test]
+    fn rejects_truncated_checkpoints() {
+        let content = get_checkpoint().get_file_content();
+
+        let error = Checkpoint::parse(&content[..content.len() - 1]).unwrap_err();
+
+        assert_eq!("truncated checkpoint", error.to_string());
+    }
+
+    #[test]
+    fn rejects_huge_images_without_their_pixels() {
+        let mut content = get_checkpoint().get_file_content();
+        let size_offset = content.len() - 6 * PIXEL_SIZE - 8;
+        content.truncate(size_offset);
+        content.extend_from_slice(&65535u32.to_le_bytes());
+        content.extend_from_slice(&65535u32.to_le_bytes());
+
+        let error = Checkpoint::parse(&content).unwrap_err();
+
+        assert_eq!("truncated checkpoint", error.to_string());
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let mut content = get_checkpoint().get_file_content();
+        content[8] = 2;
+
+        let error = Checkpoint::parse(&content).unwrap_err();
+
+        assert_eq!("unsupported checkpoint version 2", error.to_string());
+    }
+}
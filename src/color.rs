@@ -49,12 +49,12 @@ impl From<(f64, f64, f64)> for Color {
     }
 }
 
-impl<T> Into<(T, T, T)> for Color
+impl<T> From<Color> for (T, T, T)
 where
     T: From<u8>,
 {
-    fn into(self) -> (T, T, T) {
-        (self.r.into(), self.g.into(), self.b.into())
+    fn from(color: Color) -> (T, T, T) {
+        (color.r.into(), color.g.into(), color.b.into())
     }
 }
 
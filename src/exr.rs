@@ -0,0 +1,408 @@
+use crate::radiance::Radiance;
+use miniz_oxide::deflate::compress_to_vec_zlib;
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const ZIP_LINES_PER_BLOCK: u32 = 16;
+const ZIP_COMPRESSION_LEVEL: u8 = 6;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PixelType {
+    Half,
+    Float,
+}
+
+impl PixelType {
+    fn id(self) -> i32 {
+        match self {
+            PixelType::Half => 1,
+            PixelType::Float => 2,
+        }
+    }
+
+    fn push_value(self, bytes: &mut Vec<u8>, value: f64) {
+        match self {
+            PixelType::Half => bytes.extend_from_slice(&to_half(value as f32).to_le_bytes()),
+            PixelType::Float => bytes.extend_from_slice(&(value as f32).to_le_bytes()),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Compression {
+    None,
+    Zip,
+}
+
+impl Compression {
+    fn id(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Zip => 3,
+        }
+    }
+
+    fn lines_per_block(self) -> u32 {
+        match self {
+            Compression::None => 1,
+            Compression::Zip => ZIP_LINES_PER_BLOCK,
+        }
+    }
+}
+
+/// A set of RGB channels. The main image has an empty name, other layers (such
+/// as AOVs) get their channels prefixed with `name.`.
+pub struct Layer<'a> {
+    pub name: &'a str,
+    pub pixels: &'a dyn Fn(u32, u32) -> Radiance,
+}
+
+type Component = fn(&Radiance) -> f64;
+
+struct Channel<'a> {
+    name: String,
+    layer: &'a Layer<'a>,
+    component: Component,
+}
+
+/// Converts to IEEE 754 half precision, rounding to nearest even.
+fn to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        let nan_bit = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan_bit;
+    }
+
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - half_exponent) as u32;
+        let round_bit = 1 << (shift - 1);
+        let mut half_mantissa = mantissa >> shift;
+        if mantissa & round_bit != 0 && mantissa & (3 * round_bit - 1) != 0 {
+            half_mantissa += 1;
+        }
+        return sign | half_mantissa as u16;
+    }
+
+    let mut half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+    let round_bit = 0x1000;
+    if mantissa & round_bit != 0 && mantissa & (3 * round_bit - 1) != 0 {
+        half += 1;
+    }
+    sign | half as u16
+}
+
+fn push_attribute(header: &mut Vec<u8>, name: &str, attribute_type: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(attribute_type.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+fn get_channels<'a>(layers: &'a [Layer<'a>]) -> Vec<Channel<'a>> {
+    let components: [(&str, Component); 3] = [("R", |r| r.r), ("G", |r| r.g), ("B", |r| r.b)];
+
+    let mut channels: Vec<Channel> = layers
+        .iter()
+        .flat_map(|layer| {
+            components.iter().map(move |&(suffix, component)| Channel {
+                name: if layer.name.is_empty() {
+                    suffix.to_string()
+                } else {
+                    format!("{}.{}", layer.name, suffix)
+                },
+                layer,
+                component,
+            })
+        })
+        .collect();
+
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+    channels
+}
+
+fn get_header(
+    width: u32,
+    height: u32,
+    channels: &[Channel],
+    pixel_type: PixelType,
+    compression: Compression,
+) -> Vec<u8> {
+    let mut channel_list = Vec::new();
+    for channel in channels {
+        channel_list.extend_from_slice(channel.name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend_from_slice(&pixel_type.id().to_le_bytes());
+        channel_list.extend_from_slice(&[0, 0, 0, 0]);
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channel_list.push(0);
+
+    let mut window = Vec::new();
+    for coordinate in &[0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&coordinate.to_le_bytes());
+    }
+
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&2u32.to_le_bytes());
+    push_attribute(&mut header, "channels", "chlist", &channel_list);
+    push_attribute(
+        &mut header,
+        "compression",
+        "compression",
+        &[compression.id()],
+    );
+    push_attribute(&mut header, "dataWindow", "box2i", &window);
+    push_attribute(&mut header, "displayWindow", "box2i", &window);
+    push_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    push_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    push_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    push_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    header
+}
+
+/// Splits even and odd bytes then delta-encodes them, as expected by the ZIP
+/// compression of OpenEXR.
+fn zip_predictor(data: &[u8]) -> Vec<u8> {
+    let mut reordered: Vec<u8> = data.iter().step_by(2).copied().collect();
+    reordered.extend(data.iter().skip(1).step_by(2));
+
+    let mut previous = reordered.first().copied().unwrap_or(0);
+    for byte in reordered.iter_mut().skip(1) {
+        let current = *byte;
+        *byte = current.wrapping_sub(previous).wrapping_add(128);
+        previous = current;
+    }
+    reordered
+}
+
+fn compress(data: Vec<u8>, compression: Compression) -> Vec<u8> {
+    match compression {
+        Compression::None => data,
+        Compression::Zip => {
+            let compressed = compress_to_vec_zlib(&zip_predictor(&data), ZIP_COMPRESSION_LEVEL);
+            if compressed.len() < data.len() {
+                compressed
+            } else {
+                data
+            }
+        }
+    }
+}
+
+/// Builds a scanline OpenEXR image holding linear values. Images are stored
+/// from top to bottom, so row `height - 1` of `pixels` comes first.
+pub fn get_layered_file_content(
+    width: u32,
+    height: u32,
+    layers: &[Layer],
+    pixel_type: PixelType,
+    compression: Compression,
+) -> Vec<u8> {
+    let channels = get_channels(layers);
+    let mut content = get_header(width, height, &channels, pixel_type, compression);
+
+    let lines_per_block = compression.lines_per_block();
+    let mut blocks = Vec::new();
+    for first_line in (0..height).step_by(lines_per_block as usize) {
+        let mut data = Vec::new();
+        for line in first_line..(first_line + lines_per_block).min(height) {
+            let y = height - 1 - line;
+            for channel in &channels {
+                for x in 0..width {
+                    let value = (channel.component)(&(channel.layer.pixels)(x, y));
+                    pixel_type.push_value(&mut data, value);
+                }
+            }
+        }
+        blocks.push((first_line, compress(data, compression)));
+    }
+
+    let offset_table_size = blocks.len() * 8;
+    let mut offset = (content.len() + offset_table_size) as u64;
+    for (_, data) in &blocks {
+        content.extend_from_slice(&offset.to_le_bytes());
+        offset += 8 + data.len() as u64;
+    }
+
+    for (first_line, data) in blocks {
+        content.extend_from_slice(&(first_line as i32).to_le_bytes());
+        content.extend_from_slice(&(data.len() as i32).to_le_bytes());
+        content.extend_from_slice(&data);
+    }
+
+    content
+}
+
+pub fn get_file_content<F>(
+    width: u32,
+    height: u32,
+    pixels: F,
+    pixel_type: PixelType,
+    compression: Compression,
+) -> Vec<u8>
+where
+    F: Fn(u32, u32) -> Radiance,
+{
+    let layer = Layer {
+        name: "",
+        pixels: &pixels,
+    };
+    get_layered_file_content(width, height, &[layer], pixel_type, compression)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniz_oxide::inflate::decompress_to_vec_zlib;
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        let mut value = [0; 4];
+        value.copy_from_slice(&bytes[offset..offset + 4]);
+        u32::from_le_bytes(value)
+    }
+
+    fn header_end(content: &[u8]) -> usize {
+        let mut position = 8;
+        while content[position] != 0 {
+            while content[position] != 0 {
+                position += 1;
+            }
+            position += 1;
+            while content[position] != 0 {
+                position += 1;
+            }
+            position += 1;
+            let size = read_u32(content, position) as usize;
+            position += 4 + size;
+        }
+        position + 1
+    }
+
+    #[test]
+    fn converts_to_half_precision() {
+        assert_eq!(0x0000, to_half(0.));
+        assert_eq!(0x3c00, to_half(1.));
+        assert_eq!(0x3800, to_half(0.5));
+        assert_eq!(0xc000, to_half(-2.));
+        assert_eq!(0x7bff, to_half(65504.));
+        assert_eq!(0x7c00, to_half(1e6));
+        assert_eq!(0x0001, to_half(2f32.powi(-24)));
+        assert_eq!(0x3c00, to_half(1.0002));
+        assert_eq!(0x3c01, to_half(1.0009));
+    }
+
+    #[test]
+    fn sorts_layer_channels_by_name() {
+        let beauty = |_, _| Radiance::default();
+        let layers = [
+            Layer {
+                name: "normal",
+                pixels: &beauty,
+            },
+            Layer {
+                name: "",
+                pixels: &beauty,
+            },
+        ];
+
+        let names: Vec<String> = get_channels(&layers)
+            .into_iter()
+            .map(|channel| channel.name)
+            .collect();
+
+        assert_eq!(
+            vec!["B", "G", "R", "normal.B", "normal.G", "normal.R"],
+            names
+        );
+    }
+
+    #[test]
+    fn writes_magic_and_channel_list() {
+        let content = get_file_content(
+            1,
+            1,
+            |_, _| Radiance::default(),
+            PixelType::Half,
+            Compression::None,
+        );
+
+        assert_eq!(&MAGIC, &content[0..4]);
+        assert_eq!(2, read_u32(&content, 4));
+        assert_eq!(b"channels\0chlist\0", &content[8..24]);
+    }
+
+    #[test]
+    fn writes_uncompressed_float_scanlines_from_the_top() {
+        let content = get_file_content(
+            2,
+            2,
+            |x, y| Radiance::new(x as f64, y as f64, 2.),
+            PixelType::Float,
+            Compression::None,
+        );
+
+        let table = header_end(&content);
+        let first_chunk = read_u32(&content, table) as usize;
+        assert_eq!(table + 2 * 8, first_chunk);
+        assert_eq!(0, read_u32(&content, first_chunk));
+        assert_eq!(2 * 3 * 4, read_u32(&content, first_chunk + 4));
+
+        let values: Vec<f32> = (0..6)
+            .map(|i| f32::from_bits(read_u32(&content, first_chunk + 8 + 4 * i)))
+            .collect();
+        assert_eq!(vec![2., 2., 1., 1., 0., 1.], values);
+    }
+
+    #[test]
+    fn writes_zip_blocks_of_sixteen_lines() {
+        let content = get_file_content(
+            64,
+            20,
+            |_, _| Radiance::new(1., 1., 1.),
+            PixelType::Half,
+            Compression::Zip,
+        );
+
+        let table = header_end(&content);
+        let first_chunk = read_u32(&content, table) as usize;
+        let second_chunk = read_u32(&content, table + 8) as usize;
+        let size = read_u32(&content, first_chunk + 4) as usize;
+        assert_eq!(first_chunk + 8 + size, second_chunk);
+        assert_eq!(16, read_u32(&content, second_chunk));
+
+        let decompressed = decompress_to_vec_zlib(&content[first_chunk + 8..second_chunk]).unwrap();
+        assert_eq!(16 * 3 * 64 * 2, decompressed.len());
+    }
+
+    #[test]
+    fn predicts_bytes_for_zip_compression() {
+        assert_eq!(vec![1, 129, 129, 128], zip_predictor(&[1, 3, 2, 3]));
+    }
+}
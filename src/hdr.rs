@@ -0,0 +1,180 @@
+use crate::radiance::Radiance;
+
+const MIN_RLE_WIDTH: u32 = 8;
+const MAX_RLE_WIDTH: u32 = 0x7fff;
+const MIN_RUN_LENGTH: usize = 4;
+const MAX_RUN_LENGTH: usize = 127;
+const MAX_LITERAL_LENGTH: usize = 128;
+
+/// Splits a value in a mantissa in [0.5, 1) and a power of two.
+fn frexp(value: f64) -> (f64, i32) {
+    let bits = value.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i32 - 1022;
+    let mantissa = f64::from_bits((bits & !(0x7ff << 52)) | (1022 << 52));
+    (mantissa, exponent)
+}
+
+/// Encodes a radiance with a shared exponent. Negative values are clamped to 0.
+fn to_rgbe(radiance: Radiance) -> [u8; 4] {
+    let r = radiance.r.max(0.);
+    let g = radiance.g.max(0.);
+    let b = radiance.b.max(0.);
+
+    let brightest = r.max(g).max(b);
+    if brightest < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    let (mantissa, exponent) = frexp(brightest);
+    let scale = mantissa * 256. / brightest;
+    [
+        (r * scale) as u8,
+        (g * scale) as u8,
+        (b * scale) as u8,
+        (exponent + 128).clamp(0, 255) as u8,
+    ]
+}
+
+fn run_length(data: &[u8], start: usize, limit: usize) -> usize {
+    data[start..]
+        .iter()
+        .take(limit)
+        .take_while(|&&byte| byte == data[start])
+        .count()
+}
+
+/// Run length encodes one component of a scanline. Runs are stored as a count
+/// above 128 followed by the repeated byte, literals as a count followed by
+/// the bytes.
+fn push_rle_component(content: &mut Vec<u8>, data: &[u8]) {
+    let mut position = 0;
+    while position < data.len() {
+        let run = run_length(data, position, MAX_RUN_LENGTH);
+        if run >= MIN_RUN_LENGTH {
+            content.push((128 + run) as u8);
+            content.push(data[position]);
+            position += run;
+            continue;
+        }
+
+        let start = position;
+        while position < data.len()
+            && position - start < MAX_LITERAL_LENGTH
+            && run_length(data, position, MIN_RUN_LENGTH) < MIN_RUN_LENGTH
+        {
+            position += 1;
+        }
+        content.push((position - start) as u8);
+        content.extend_from_slice(&data[start..position]);
+    }
+}
+
+fn push_scanline(content: &mut Vec<u8>, scanline: &[[u8; 4]]) {
+    let width = scanline.len() as u32;
+    if !(MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width) {
+        content.extend(scanline.iter().flatten());
+        return;
+    }
+
+    content.extend_from_slice(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8]);
+    for component in 0..4 {
+        let data: Vec<u8> = scanline.iter().map(|rgbe| rgbe[component]).collect();
+        push_rle_component(content, &data);
+    }
+}
+
+/// Builds a Radiance RGBE image holding linear values, with the same pixel
+/// order as the PPM writer.
+pub fn get_file_content<F>(width: u32, height: u32, pixels: F) -> Vec<u8>
+where
+    F: Fn(u32, u32) -> Radiance,
+{
+    let mut content = format!(
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )
+    .into_bytes();
+
+    for y in (0u32..height).rev() {
+        let scanline: Vec<[u8; 4]> = (0u32..width).map(|x| to_rgbe(pixels(x, y))).collect();
+        push_scanline(&mut content, &scanline);
+    }
+
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_rle_component(encoded: &[u8], length: usize) -> (Vec<u8>, usize) {
+        let mut data = Vec::new();
+        let mut position = 0;
+        while data.len() < length {
+            let count = encoded[position] as usize;
+            if count > 128 {
+                data.extend(std::iter::repeat_n(encoded[position + 1], count - 128));
+                position += 2;
+            } else {
+                data.extend_from_slice(&encoded[position + 1..position + 1 + count]);
+                position += 1 + count;
+            }
+        }
+        (data, position)
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn splits_mantissa_and_exponent() {
+        assert_eq!((0.5, 1), frexp(1.));
+        assert_eq!((0.75, 2), frexp(3.));
+        assert_eq!((0.5, -1), frexp(0.25));
+    }
+
+    #[test]
+    fn encodes_shared_exponent() {
+        assert_eq!([128, 128, 128, 129], to_rgbe(Radiance::new(1., 1., 1.)));
+        assert_eq!([128, 64, 0, 131], to_rgbe(Radiance::new(4., 2., -1.)));
+        assert_eq!([0, 0, 0, 0], to_rgbe(Radiance::default()));
+    }
+
+    #[test]
+    fn encodes_runs_and_literals() {
+        let mut content = Vec::new();
+
+        push_rle_component(&mut content, &[1, 2, 3, 7, 7, 7, 7, 7, 4]);
+
+        assert_eq!(vec![3, 1, 2, 3, 133, 7, 1, 4], content);
+    }
+
+    #[test]
+    fn decodes_back_long_scanline_components() {
+        let data: Vec<u8> = (0..600)
+            .map(|i| if i < 300 { 9 } else { i as u8 })
+            .collect();
+        let mut content = Vec::new();
+
+        push_rle_component(&mut content, &data);
+
+        assert_eq!((data, content.len()), decode_rle_component(&content, 600));
+    }
+
+    #[test]
+    fn writes_header_and_rle_scanlines() {
+        let content = get_file_content(8, 1, |_, _| Radiance::new(1., 1., 1.));
+
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 8\n";
+        assert_eq!(&header[..], &content[..header.len()]);
+        assert_eq!(
+            &[2, 2, 0, 8, 136, 128, 136, 128, 136, 128, 136, 129],
+            &content[header.len()..]
+        );
+    }
+
+    #[test]
+    fn writes_flat_scanlines_from_the_top_when_too_narrow() {
+        let content = get_file_content(1, 2, |_, y| Radiance::new(y as f64, 0., 0.));
+
+        assert_eq!(&[128, 0, 0, 129, 0, 0, 0, 0], &content[content.len() - 8..]);
+    }
+}
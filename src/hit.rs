@@ -1,16 +1,105 @@
+use crate::aabb::Aabb;
 use crate::material::Material;
 use crate::ray::Ray;
-use crate::vector3::Vector3;
+use crate::vector3::{random_in_cone, Vector3};
+use rand::{Rng, RngCore};
+use std::f64::consts;
+use std::sync::Arc;
+
+/// Offset applied to rays leaving a surface, so that they do not hit it again.
+pub const SURFACE_OFFSET: f64 = 0.001;
 
 pub struct HitRecord<'a> {
     pub t: f64,
     pub point: Vector3,
     pub normal: Vector3,
+    pub uv: (f64, f64),
     pub material: &'a dyn Material,
 }
 
-pub trait Hittable {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+/// Point picked on the surface of an object. `pdf` is the density of its
+/// direction over solid angle, seen from the origin of the sampling.
+#[derive(Debug, Copy, Clone)]
+pub struct SurfaceSample {
+    pub point: Vector3,
+    pub normal: Vector3,
+    pub pdf: f64,
+}
+
+pub trait Hittable: Send + Sync {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// Like `hit`, but also finds the random hits of participating media,
+    /// drawn from `rng`. Objects containing others pass `rng` on.
+    fn sample_hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        _rng: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        self.hit(ray, t_min, t_max)
+    }
+
+    /// Picks a point of the surface seen from `origin`, for objects which can
+    /// be sampled as lights.
+    fn sample_surface(&self, _origin: &Vector3, _rng: &mut dyn RngCore) -> Option<SurfaceSample> {
+        None
+    }
+
+    /// Density over solid angle of `sample_surface` picking the point seen in
+    /// `direction` from `origin`.
+    fn surface_pdf(&self, _origin: &Vector3, _direction: &Vector3) -> f64 {
+        0.
+    }
+}
+
+/// Lets one object be referenced from several places of a scene.
+impl<T: Hittable + ?Sized> Hittable for Arc<T> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        (**self).hit(ray, t_min, t_max)
+    }
+
+    fn sample_hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        (**self).sample_hit(ray, t_min, t_max, rng)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+
+    fn sample_surface(&self, origin: &Vector3, rng: &mut dyn RngCore) -> Option<SurfaceSample> {
+        (**self).sample_surface(origin, rng)
+    }
+
+    fn surface_pdf(&self, origin: &Vector3, direction: &Vector3) -> f64 {
+        (**self).surface_pdf(origin, direction)
+    }
+}
+
+/// Solid angle seen from `origin` per unit of area of a surface at `point`.
+/// Dividing a density over area by it gives a density over solid angle.
+pub fn solid_angle_per_area(origin: &Vector3, point: &Vector3, normal: &Vector3) -> f64 {
+    let to_point = *point - *origin;
+    let squared_distance = to_point.squared_norm();
+    to_point.dot(normal).abs() / (normal.norm() * squared_distance * squared_distance.sqrt())
+}
+
+/// Density over solid angle of a point picked uniformly on a surface of `area`.
+pub fn uniform_area_pdf(area: f64, origin: &Vector3, point: &Vector3, normal: &Vector3) -> f64 {
+    let solid_angle = solid_angle_per_area(origin, point, normal);
+    if solid_angle > 0. {
+        1. / (area * solid_angle)
+    } else {
+        0.
+    }
 }
 
 pub struct HittableList {
@@ -21,20 +110,86 @@ impl HittableList {
     pub fn new(list: Vec<Box<dyn Hittable>>) -> Self {
         HittableList { list }
     }
+
+    pub fn into_objects(self) -> Vec<Box<dyn Hittable>> {
+        self.list
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+}
+
+/// Closest of the hits found by `hit_object` on `objects`, given the current
+/// `t_max`.
+pub(crate) fn closest_hit<'a>(
+    objects: &'a [Box<dyn Hittable>],
+    t_max: f64,
+    mut hit_object: impl FnMut(&'a dyn Hittable, f64) -> Option<HitRecord<'a>>,
+) -> Option<HitRecord<'a>> {
+    objects
+        .iter()
+        .fold((t_max, None), |(closest_t, current_hit), object| {
+            if let Some(new_hit) = hit_object(object.as_ref(), closest_t) {
+                (new_hit.t, Some(new_hit))
+            } else {
+                (closest_t, current_hit)
+            }
+        })
+        .1
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        self.list
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        closest_hit(&self.list, t_max, |object, closest_t| {
+            object.hit(ray, t_min, closest_t)
+        })
+    }
+
+    fn sample_hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        closest_hit(&self.list, t_max, |object, closest_t| {
+            object.sample_hit(ray, t_min, closest_t, rng)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut boxes = self.list.iter().map(|object| object.bounding_box());
+        let first_box = boxes.next()??;
+        boxes.try_fold(first_box, |surrounding, object_box| {
+            Some(surrounding.surrounding(&object_box?))
+        })
+    }
+
+    /// Samples one of the objects, chosen uniformly.
+    fn sample_surface(&self, origin: &Vector3, rng: &mut dyn RngCore) -> Option<SurfaceSample> {
+        if self.list.is_empty() {
+            return None;
+        }
+        let index = rng.gen_range(0, self.list.len());
+        let sample = self.list[index].sample_surface(origin, rng)?;
+
+        Some(SurfaceSample {
+            pdf: self.surface_pdf(origin, &(sample.point - *origin)),
+            ..sample
+        })
+    }
+
+    fn surface_pdf(&self, origin: &Vector3, direction: &Vector3) -> f64 {
+        if self.list.is_empty() {
+            return 0.;
+        }
+        let pdf_sum: f64 = self
+            .list
             .iter()
-            .fold((t_max, None), |(closest_t, current_hit), b| {
-                if let Some(new_hit) = b.hit(&ray, t_min, closest_t) {
-                    (new_hit.t, Some(new_hit))
-                } else {
-                    (closest_t, current_hit)
-                }
-            })
-            .1
+            .map(|object| object.surface_pdf(origin, direction))
+            .sum();
+        pdf_sum / self.list.len() as f64
     }
 }
 
@@ -54,51 +209,471 @@ impl Sphere {
     }
 }
 
-impl Sphere {
-    fn get_hit_in_range(&self, ray: &Ray, t_min: f64, t_max: f64, hit: f64) -> Option<HitRecord> {
-        if t_min < hit && hit < t_max {
-            let hit_point = ray.point_at_parameter(hit);
-            Some(HitRecord {
-                t: hit,
-                point: hit_point,
-                normal: (hit_point - self.center) / self.radius,
-                material: &(*self.material),
+/// Maps a point of the unit sphere to its longitude and latitude, both in
+/// [0, 1]. `u` starts at -X and turns around Y, `v` goes from -Y to +Y.
+fn get_sphere_uv(point: Vector3) -> (f64, f64) {
+    let phi = point.z.atan2(point.x);
+    let theta = point.y.clamp(-1., 1.).asin();
+    (
+        1. - (phi + consts::PI) / (2. * consts::PI),
+        (theta + consts::FRAC_PI_2) / consts::PI,
+    )
+}
+
+fn get_sphere_hit_in_range<'a>(
+    center: Vector3,
+    radius: f64,
+    material: &'a dyn Material,
+    ray: &Ray,
+    (t_min, t_max): (f64, f64),
+    hit: f64,
+) -> Option<HitRecord<'a>> {
+    if t_min < hit && hit < t_max {
+        let hit_point = ray.point_at_parameter(hit);
+        Some(HitRecord {
+            t: hit,
+            point: hit_point,
+            normal: (hit_point - center) / radius,
+            uv: get_sphere_uv((hit_point - center) / radius.abs()),
+            material,
+        })
+    } else {
+        None
+    }
+}
+
+fn hit_sphere<'a>(
+    center: Vector3,
+    radius: f64,
+    material: &'a dyn Material,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord<'a>> {
+    let sphere_to_ray_origin = ray.origin - center;
+    let a = ray.direction.dot(&ray.direction);
+    let b = sphere_to_ray_origin.dot(&ray.direction);
+    let c = sphere_to_ray_origin.dot(&sphere_to_ray_origin) - radius * radius;
+    let discriminant = b * b - a * c;
+
+    if discriminant < 0. {
+        None
+    } else {
+        let range = (t_min, t_max);
+        let first_hit = (-b - discriminant.sqrt()) / a;
+        get_sphere_hit_in_range(center, radius, material, ray, range, first_hit).or({
+            let second_hit = (-b + discriminant.sqrt()) / a;
+            get_sphere_hit_in_range(center, radius, material, ray, range, second_hit)
+        })
+    }
+}
+
+fn get_sphere_bounding_box(center: Vector3, radius: f64) -> Aabb {
+    let radius = radius.abs();
+    let extent = Vector3::from((radius, radius, radius));
+    Aabb::new(center - extent, center + extent)
+}
+
+/// Cosine of the half angle of the cone under which the sphere is seen from
+/// `origin`, if it lies outside.
+fn get_sphere_cone(center: Vector3, radius: f64, origin: &Vector3) -> Option<f64> {
+    let squared_distance = (center - *origin).squared_norm();
+    let squared_radius = radius * radius;
+    if squared_distance <= squared_radius {
+        None
+    } else {
+        Some((1. - squared_radius / squared_distance).sqrt())
+    }
+}
+
+fn get_cone_pdf(cos_theta_max: f64) -> f64 {
+    1. / (2. * consts::PI * (1. - cos_theta_max))
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_sphere(
+            self.center,
+            self.radius,
+            &(*self.material),
+            ray,
+            t_min,
+            t_max,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(get_sphere_bounding_box(self.center, self.radius))
+    }
+
+    /// Picks a direction uniformly in the cone under which the sphere is seen,
+    /// which never wastes samples on its hidden side.
+    fn sample_surface(&self, origin: &Vector3, rng: &mut dyn RngCore) -> Option<SurfaceSample> {
+        let cos_theta_max = get_sphere_cone(self.center, self.radius, origin)?;
+        let axis = (self.center - *origin).normalized();
+        let ray = Ray::new(*origin, random_in_cone(&axis, cos_theta_max, rng));
+        let hit = self.hit(&ray, 0., f64::MAX)?;
+
+        Some(SurfaceSample {
+            point: hit.point,
+            normal: hit.normal,
+            pdf: get_cone_pdf(cos_theta_max),
+        })
+    }
+
+    fn surface_pdf(&self, origin: &Vector3, direction: &Vector3) -> f64 {
+        match get_sphere_cone(self.center, self.radius, origin) {
+            Some(cos_theta_max)
+                if self
+                    .hit(&Ray::new(*origin, *direction), SURFACE_OFFSET, f64::MAX)
+                    .is_some() =>
+            {
+                get_cone_pdf(cos_theta_max)
+            }
+            _ => 0.,
+        }
+    }
+}
+
+/// A sphere moving linearly from `center_0` at `time_0` to `center_1` at
+/// `time_1`, and staying still before and after, so that its bounding box
+/// covers it whatever the shutter interval of the camera.
+pub struct MovingSphere {
+    center_0: Vector3,
+    center_1: Vector3,
+    time_0: f64,
+    time_1: f64,
+    radius: f64,
+    material: Box<dyn Material>,
+}
+
+impl MovingSphere {
+    pub fn new(
+        (center_0, time_0): (Vector3, f64),
+        (center_1, time_1): (Vector3, f64),
+        radius: f64,
+        material: Box<dyn Material>,
+    ) -> Self {
+        assert!(time_0 < time_1);
+
+        MovingSphere {
+            center_0,
+            center_1,
+            time_0,
+            time_1,
+            radius,
+            material,
+        }
+    }
+
+    pub fn center(&self, time: f64) -> Vector3 {
+        let t = ((time - self.time_0) / (self.time_1 - self.time_0)).clamp(0., 1.);
+        self.center_0 + (self.center_1 - self.center_0) * t
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_sphere(
+            self.center(ray.time),
+            self.radius,
+            &(*self.material),
+            ray,
+            t_min,
+            t_max,
+        )
+    }
+
+    /// Covers the whole motion between `time_0` and `time_1`.
+    fn bounding_box(&self) -> Option<Aabb> {
+        let box_0 = get_sphere_bounding_box(self.center_0, self.radius);
+        let box_1 = get_sphere_bounding_box(self.center_1, self.radius);
+        Some(box_0.surrounding(&box_1))
+    }
+}
+
+pub struct FlipNormals {
+    object: Box<dyn Hittable>,
+}
+
+impl FlipNormals {
+    pub fn new(object: Box<dyn Hittable>) -> Self {
+        FlipNormals { object }
+    }
+}
+
+fn flip_normal(hit: HitRecord<'_>) -> HitRecord<'_> {
+    HitRecord {
+        normal: -hit.normal,
+        ..hit
+    }
+}
+
+impl Hittable for FlipNormals {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.object.hit(ray, t_min, t_max).map(flip_normal)
+    }
+
+    fn sample_hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        self.object
+            .sample_hit(ray, t_min, t_max, rng)
+            .map(flip_normal)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+
+    fn sample_surface(&self, origin: &Vector3, rng: &mut dyn RngCore) -> Option<SurfaceSample> {
+        self.object
+            .sample_surface(origin, rng)
+            .map(|sample| SurfaceSample {
+                normal: -sample.normal,
+                ..sample
+            })
+    }
+
+    fn surface_pdf(&self, origin: &Vector3, direction: &Vector3) -> f64 {
+        self.object.surface_pdf(origin, direction)
+    }
+}
+
+fn unit_axis(axis: usize, sign: f64) -> Vector3 {
+    let mut coordinates = [0.; 3];
+    coordinates[axis] = sign;
+    Vector3::from((coordinates[0], coordinates[1], coordinates[2]))
+}
+
+/// Rectangle lying in the plane `normal_axis = k`, spanning the given ranges on
+/// the two other axes.
+#[derive(Debug, Copy, Clone)]
+struct AxisAlignedRect {
+    axes: (usize, usize, usize),
+    first_range: (f64, f64),
+    second_range: (f64, f64),
+    k: f64,
+    normal: Vector3,
+}
+
+impl AxisAlignedRect {
+    fn new(
+        axes: (usize, usize, usize),
+        first_range: (f64, f64),
+        second_range: (f64, f64),
+        k: f64,
+    ) -> Self {
+        AxisAlignedRect {
+            axes,
+            first_range,
+            second_range,
+            k,
+            normal: unit_axis(axes.2, 1.),
+        }
+    }
+
+    fn flipped(self) -> Self {
+        AxisAlignedRect {
+            normal: -self.normal,
+            ..self
+        }
+    }
+
+    fn hit<'a>(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        material: &'a dyn Material,
+    ) -> Option<HitRecord<'a>> {
+        let (first_axis, second_axis, normal_axis) = self.axes;
+
+        let t = (self.k - ray.origin[normal_axis]) / ray.direction[normal_axis];
+        if !(t_min < t && t < t_max) {
+            return None;
+        }
+
+        let point = ray.point_at_parameter(t);
+        let (first_min, first_max) = self.first_range;
+        let (second_min, second_max) = self.second_range;
+        let first = point[first_axis];
+        let second = point[second_axis];
+        if first < first_min || first > first_max || second < second_min || second > second_max {
+            return None;
+        }
+
+        Some(HitRecord {
+            t,
+            point,
+            normal: self.normal,
+            uv: (
+                (first - first_min) / (first_max - first_min),
+                (second - second_min) / (second_max - second_min),
+            ),
+            material,
+        })
+    }
+
+    fn area(&self) -> f64 {
+        (self.first_range.1 - self.first_range.0) * (self.second_range.1 - self.second_range.0)
+    }
+
+    fn sample_surface(&self, origin: &Vector3, rng: &mut dyn RngCore) -> Option<SurfaceSample> {
+        let (first_axis, second_axis, normal_axis) = self.axes;
+        let mut coordinates = [0.; 3];
+        coordinates[first_axis] = rng.gen_range(self.first_range.0, self.first_range.1);
+        coordinates[second_axis] = rng.gen_range(self.second_range.0, self.second_range.1);
+        coordinates[normal_axis] = self.k;
+        let point = Vector3::from((coordinates[0], coordinates[1], coordinates[2]));
+
+        let pdf = uniform_area_pdf(self.area(), origin, &point, &self.normal);
+        if pdf > 0. {
+            Some(SurfaceSample {
+                point,
+                normal: self.normal,
+                pdf,
             })
         } else {
             None
         }
     }
-}
 
-impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let sphere_to_ray_origin = ray.origin - self.center;
-        let a = ray.direction.dot(&ray.direction);
-        let b = sphere_to_ray_origin.dot(&ray.direction);
-        let c = sphere_to_ray_origin.dot(&sphere_to_ray_origin) - self.radius * self.radius;
-        let discriminant = b * b - a * c;
-
-        if discriminant < 0. {
-            None
-        } else {
-            let first_hit = (-b - discriminant.sqrt()) / a;
-            self.get_hit_in_range(ray, t_min, t_max, first_hit).or({
-                let second_hit = (-b + discriminant.sqrt()) / a;
-                self.get_hit_in_range(ray, t_min, t_max, second_hit)
+    fn surface_pdf(&self, origin: &Vector3, direction: &Vector3, material: &dyn Material) -> f64 {
+        let ray = Ray::new(*origin, *direction);
+        self.hit(&ray, SURFACE_OFFSET, f64::MAX, material)
+            .map_or(0., |hit| {
+                uniform_area_pdf(self.area(), origin, &hit.point, &self.normal)
             })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let (first_axis, second_axis, normal_axis) = self.axes;
+        let mut min = [0.; 3];
+        let mut max = [0.; 3];
+        min[first_axis] = self.first_range.0;
+        max[first_axis] = self.first_range.1;
+        min[second_axis] = self.second_range.0;
+        max[second_axis] = self.second_range.1;
+        min[normal_axis] = self.k;
+        max[normal_axis] = self.k;
+
+        Aabb::new(
+            Vector3::from((min[0], min[1], min[2])),
+            Vector3::from((max[0], max[1], max[2])),
+        )
+    }
+}
+
+macro_rules! axis_aligned_rect {
+    ($name:ident, $first:ident, $second:ident, $axes:expr) => {
+        pub struct $name {
+            rect: AxisAlignedRect,
+            material: Box<dyn Material>,
+        }
+
+        impl $name {
+            pub fn new(
+                $first: (f64, f64),
+                $second: (f64, f64),
+                k: f64,
+                material: Box<dyn Material>,
+            ) -> Self {
+                $name {
+                    rect: AxisAlignedRect::new($axes, $first, $second, k),
+                    material,
+                }
+            }
+        }
+
+        impl Hittable for $name {
+            fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+                self.rect.hit(ray, t_min, t_max, &(*self.material))
+            }
+
+            fn bounding_box(&self) -> Option<Aabb> {
+                Some(self.rect.bounding_box())
+            }
+
+            fn sample_surface(
+                &self,
+                origin: &Vector3,
+                rng: &mut dyn RngCore,
+            ) -> Option<SurfaceSample> {
+                self.rect.sample_surface(origin, rng)
+            }
+
+            fn surface_pdf(&self, origin: &Vector3, direction: &Vector3) -> f64 {
+                self.rect.surface_pdf(origin, direction, &(*self.material))
+            }
+        }
+    };
+}
+
+axis_aligned_rect!(XYRect, x_range, y_range, (0, 1, 2));
+axis_aligned_rect!(XZRect, x_range, z_range, (0, 2, 1));
+axis_aligned_rect!(YZRect, y_range, z_range, (1, 2, 0));
+
+/// Axis-aligned box made of six rectangles with normals pointing outward.
+pub struct BoxShape {
+    sides: [AxisAlignedRect; 6],
+    bounding_box: Aabb,
+    material: Box<dyn Material>,
+}
+
+impl BoxShape {
+    pub fn new(min: Vector3, max: Vector3, material: Box<dyn Material>) -> Self {
+        let xy = |z| AxisAlignedRect::new((0, 1, 2), (min.x, max.x), (min.y, max.y), z);
+        let xz = |y| AxisAlignedRect::new((0, 2, 1), (min.x, max.x), (min.z, max.z), y);
+        let yz = |x| AxisAlignedRect::new((1, 2, 0), (min.y, max.y), (min.z, max.z), x);
+
+        BoxShape {
+            sides: [
+                xy(max.z),
+                xy(min.z).flipped(),
+                xz(max.y),
+                xz(min.y).flipped(),
+                yz(max.x),
+                yz(min.x).flipped(),
+            ],
+            bounding_box: Aabb::new(min, max),
+            material,
         }
     }
 }
 
+impl Hittable for BoxShape {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.sides
+            .iter()
+            .fold((t_max, None), |(closest_t, current_hit), side| {
+                if let Some(new_hit) = side.hit(ray, t_min, closest_t, &(*self.material)) {
+                    (new_hit.t, Some(new_hit))
+                } else {
+                    (closest_t, current_hit)
+                }
+            })
+            .1
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounding_box)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::material::Lambertian;
+    use crate::random::Pcg32;
+    use crate::texture::ConstantTexture;
 
     fn get_dummy_material() -> Box<dyn Material> {
-        Box::new(Lambertian {
-            albedo: Vector3::default(),
-        })
+        Box::new(Lambertian::new(ConstantTexture::new(Vector3::default())))
     }
 
     #[test]
@@ -131,4 +706,272 @@ mod tests {
         let hit = sphere.hit(&ray, 4., 10.);
         assert!(hit.is_none());
     }
+
+    #[test]
+    fn computes_spherical_coordinates_of_hits() {
+        let sphere = Sphere::new(Vector3::from((0., 0., -2.)), -1., get_dummy_material());
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+
+        let hit = sphere.hit(&ray, 0., 2.).unwrap();
+
+        assert_eq!((0.25, 0.5), hit.uv);
+    }
+
+    #[test]
+    fn hits_moving_sphere_at_the_position_of_ray_time() {
+        let sphere = MovingSphere::new(
+            (Vector3::from((0., 0., -2.)), 0.),
+            (Vector3::from((0., 2., -2.)), 1.),
+            0.5,
+            get_dummy_material(),
+        );
+        let direction = Vector3::from((0., 0., -1.));
+        let ray_at_start = Ray::with_time(Vector3::default(), direction, 0.);
+        let ray_at_end = Ray::with_time(Vector3::default(), direction, 1.);
+        let ray_at_middle = Ray::with_time(Vector3::from((0., 1., 0.)), direction, 0.5);
+
+        assert!(sphere.hit(&ray_at_start, 0., 10.).is_some());
+        assert!(sphere.hit(&ray_at_end, 0., 10.).is_none());
+        assert_eq!(
+            Vector3::from((0., 1., -1.5)),
+            sphere.hit(&ray_at_middle, 0., 10.).unwrap().point
+        );
+    }
+
+    #[test]
+    fn moving_sphere_bounding_box_covers_the_whole_motion() {
+        let sphere = MovingSphere::new(
+            (Vector3::from((0., 0., 0.)), 0.),
+            (Vector3::from((2., 1., 0.)), 1.),
+            1.,
+            get_dummy_material(),
+        );
+
+        let bounding_box = sphere.bounding_box().unwrap();
+
+        assert_eq!(Vector3::from((-1., -1., -1.)), bounding_box.min);
+        assert_eq!(Vector3::from((3., 2., 1.)), bounding_box.max);
+    }
+
+    #[test]
+    fn maps_poles_and_meridian_of_unit_sphere() {
+        assert_eq!(1., get_sphere_uv(Vector3::from((0., 1., 0.))).1);
+        assert_eq!(0., get_sphere_uv(Vector3::from((0., -1., 0.))).1);
+        assert_eq!((0.5, 0.5), get_sphere_uv(Vector3::from((1., 0., 0.))));
+    }
+
+    #[test]
+    fn sphere_with_negative_radius_has_a_valid_bounding_box() {
+        let sphere = Sphere::new(Vector3::from((0., 0., -2.)), -1., get_dummy_material());
+
+        let bounding_box = sphere.bounding_box().unwrap();
+
+        assert_eq!(Vector3::from((-1., -1., -3.)), bounding_box.min);
+        assert_eq!(Vector3::from((1., 1., -1.)), bounding_box.max);
+    }
+
+    #[test]
+    fn shared_objects_can_be_listed_several_times() {
+        let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(
+            Vector3::from((0., 0., -2.)),
+            1.,
+            get_dummy_material(),
+        ));
+        let list = HittableList::new(vec![Box::new(sphere.clone()), Box::new(sphere)]);
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+
+        assert_eq!(
+            Vector3::from((0., 0., -1.)),
+            list.hit(&ray, 0., 10.).unwrap().point
+        );
+    }
+
+    #[test]
+    fn list_bounding_box_surrounds_all_objects() {
+        let list = HittableList::new(vec![
+            Box::new(Sphere::new(
+                Vector3::from((0., 0., -2.)),
+                1.,
+                get_dummy_material(),
+            )),
+            Box::new(Sphere::new(
+                Vector3::from((3., 0., 0.)),
+                0.5,
+                get_dummy_material(),
+            )),
+        ]);
+
+        let bounding_box = list.bounding_box().unwrap();
+
+        assert_eq!(Vector3::from((-1., -1., -3.)), bounding_box.min);
+        assert_eq!(Vector3::from((3.5, 1., 0.5)), bounding_box.max);
+    }
+
+    #[test]
+    fn hits_xy_rect_in_its_bounds() {
+        let rect = XYRect::new((-1., 1.), (-1., 1.), -2., get_dummy_material());
+        let ray = Ray::new(Vector3::from((0.5, 0., 0.)), Vector3::from((0., 0., -1.)));
+
+        let hit = rect.hit(&ray, 0., 10.).unwrap();
+
+        assert_eq!(Vector3::from((0.5, 0., -2.)), hit.point);
+        assert_eq!(Vector3::from((0., 0., 1.)), hit.normal);
+        assert_eq!((0.75, 0.5), hit.uv);
+    }
+
+    #[test]
+    fn misses_xz_rect_out_of_its_bounds() {
+        let rect = XZRect::new((-1., 1.), (-1., 1.), 0., get_dummy_material());
+        let ray = Ray::new(Vector3::from((2., 1., 0.)), Vector3::from((0., -1., 0.)));
+
+        assert!(rect.hit(&ray, 0., 10.).is_none());
+    }
+
+    #[test]
+    fn misses_yz_rect_parallel_to_the_ray() {
+        let rect = YZRect::new((-1., 1.), (-1., 1.), 0., get_dummy_material());
+        let ray = Ray::new(Vector3::from((1., 0., 0.)), Vector3::from((0., 1., 0.)));
+
+        assert!(rect.hit(&ray, 0., 10.).is_none());
+    }
+
+    #[test]
+    fn rect_bounding_box_is_flat() {
+        let rect = XZRect::new((0., 1.), (2., 3.), 4., get_dummy_material());
+
+        let bounding_box = rect.bounding_box().unwrap();
+
+        assert_eq!(Vector3::from((0., 4., 2.)), bounding_box.min);
+        assert_eq!(Vector3::from((1., 4., 3.)), bounding_box.max);
+    }
+
+    #[test]
+    fn flips_normals() {
+        let rect = FlipNormals::new(Box::new(YZRect::new(
+            (-1., 1.),
+            (-1., 1.),
+            0.,
+            get_dummy_material(),
+        )));
+        let ray = Ray::new(Vector3::from((1., 0., 0.)), Vector3::from((-1., 0., 0.)));
+
+        let hit = rect.hit(&ray, 0., 10.).unwrap();
+
+        assert_eq!(Vector3::from((-1., 0., 0.)), hit.normal);
+    }
+
+    #[test]
+    fn hits_box_with_outward_normals() {
+        let box_shape = BoxShape::new(
+            Vector3::from((-1., -1., -1.)),
+            Vector3::from((1., 1., 1.)),
+            get_dummy_material(),
+        );
+        let from_above = Ray::new(Vector3::from((0., 5., 0.)), Vector3::from((0., -1., 0.)));
+        let from_left = Ray::new(Vector3::from((-5., 0., 0.)), Vector3::from((1., 0., 0.)));
+
+        let top_hit = box_shape.hit(&from_above, 0., 10.).unwrap();
+        let left_hit = box_shape.hit(&from_left, 0., 10.).unwrap();
+
+        assert_eq!(Vector3::from((0., 1., 0.)), top_hit.point);
+        assert_eq!(Vector3::from((0., 1., 0.)), top_hit.normal);
+        assert_eq!(Vector3::from((-1., 0., 0.)), left_hit.point);
+        assert_eq!(Vector3::from((-1., 0., 0.)), left_hit.normal);
+    }
+
+    fn assert_pdf_matches(object: &dyn Hittable, origin: Vector3) {
+        let mut rng = Pcg32::new(0, 0);
+        for _i in 0..20 {
+            let sample = object.sample_surface(&origin, &mut rng).unwrap();
+            let pdf = object.surface_pdf(&origin, &(sample.point - origin));
+            assert!(
+                (sample.pdf - pdf).abs() < 1e-9 * pdf,
+                "sampled {}, evaluated {}",
+                sample.pdf,
+                pdf
+            );
+        }
+    }
+
+    #[test]
+    fn samples_the_visible_cap_of_spheres() {
+        let mut rng = Pcg32::new(0, 0);
+        let sphere = Sphere::new(Vector3::default(), 1., get_dummy_material());
+        let origin = Vector3::from((0., 0., 2.));
+
+        for _i in 0..20 {
+            let sample = sphere.sample_surface(&origin, &mut rng).unwrap();
+            assert!((sample.point.norm() - 1.).abs() < 1e-9);
+            assert!(sample.normal.dot(&(origin - sample.point)) >= 0.);
+        }
+        let cone_pdf = 1. / (2. * consts::PI * (1. - 3f64.sqrt() / 2.));
+        let pdf = sphere.surface_pdf(&origin, &Vector3::from((0., 0., -1.)));
+        assert!((pdf - cone_pdf).abs() < 1e-9);
+        assert_eq!(
+            0.,
+            sphere.surface_pdf(&origin, &Vector3::from((0., 1., 0.)))
+        );
+        assert_pdf_matches(&sphere, origin);
+    }
+
+    #[test]
+    fn cannot_sample_spheres_from_inside() {
+        let mut rng = Pcg32::new(0, 0);
+        let sphere = Sphere::new(Vector3::default(), 1., get_dummy_material());
+
+        assert!(sphere
+            .sample_surface(&Vector3::default(), &mut rng)
+            .is_none());
+        assert_eq!(
+            0.,
+            sphere.surface_pdf(&Vector3::default(), &Vector3::from((1., 0., 0.)))
+        );
+    }
+
+    #[test]
+    fn samples_rectangles_uniformly_over_their_area() {
+        let mut rng = Pcg32::new(0, 0);
+        let rect = FlipNormals::new(Box::new(XZRect::new(
+            (-1., 1.),
+            (-2., 2.),
+            3.,
+            get_dummy_material(),
+        )));
+        let origin = Vector3::default();
+
+        let sample = rect.sample_surface(&origin, &mut rng).unwrap();
+        let pdf_below_center = rect.surface_pdf(&origin, &Vector3::from((0., 1., 0.)));
+
+        assert_eq!(3., sample.point.y);
+        assert_eq!(Vector3::from((0., -1., 0.)), sample.normal);
+        assert!((pdf_below_center - 9. / 8.).abs() < 1e-12);
+        assert_pdf_matches(&rect, origin);
+    }
+
+    #[test]
+    fn list_density_is_the_mean_of_its_objects() {
+        let list = HittableList::new(vec![
+            Box::new(Sphere::new(
+                Vector3::from((0., 0., -4.)),
+                2.,
+                get_dummy_material(),
+            )),
+            Box::new(Sphere::new(
+                Vector3::from((0., 0., 4.)),
+                2.,
+                get_dummy_material(),
+            )),
+            Box::new(Sphere::new(
+                Vector3::from((0., 0., 8.)),
+                2.,
+                get_dummy_material(),
+            )),
+        ]);
+        let origin = Vector3::default();
+
+        let pdf = list.surface_pdf(&origin, &Vector3::from((0., 0., -1.)));
+
+        assert!((pdf - get_cone_pdf(3f64.sqrt() / 2.) / 3.).abs() < 1e-9);
+        assert_pdf_matches(&list, origin);
+    }
 }
@@ -0,0 +1,384 @@
+use crate::hit::{HitRecord, Hittable, SURFACE_OFFSET};
+use crate::pdf::{HittablePdf, Pdf};
+use crate::radiance::Radiance;
+use crate::ray::Ray;
+use crate::scenes::Background;
+use crate::vector3::Vector3;
+use rand::{Rng, RngCore};
+use std::f64;
+
+/// Weight of a sample drawn with density `pdf` by one of two sampling
+/// strategies, the other one having the density `other_pdf` (Veach's power
+/// heuristic).
+pub fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let squared_pdf = pdf * pdf;
+    squared_pdf / (squared_pdf + other_pdf * other_pdf)
+}
+
+/// Light reaching a hit from a point picked on the lights, weighted against
+/// the chances of finding it with `scattering_pdf`.
+fn sample_lights(
+    ray: &Ray,
+    hit: &HitRecord,
+    scattering_pdf: &dyn Pdf,
+    world: &dyn Hittable,
+    lights: &dyn Hittable,
+    rng: &mut dyn RngCore,
+) -> Radiance {
+    let light_pdf = HittablePdf::new(lights, hit.point);
+    let direction = match light_pdf.generate(rng) {
+        Some(direction) => direction,
+        None => return Radiance::default(),
+    };
+    let bsdf = hit.material.bsdf(ray, hit, &direction);
+    if bsdf == Vector3::default() {
+        return Radiance::default();
+    }
+
+    let shadow_ray = Ray::with_time(hit.point, direction, ray.time);
+    match world.sample_hit(&shadow_ray, SURFACE_OFFSET, f64::MAX, rng) {
+        Some(light_hit) => {
+            let density = light_pdf.value(&direction);
+            if density <= 0. {
+                return Radiance::default();
+            }
+            let weight = power_heuristic(density, scattering_pdf.value(&direction));
+            light_hit.material.emitted(&light_hit) * bsdf * (weight / density)
+        }
+        None => Radiance::default(),
+    }
+}
+
+/// Computes the light arriving at the camera along rays.
+pub trait Integrator: Send + Sync {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+        background: &Background,
+        rng: &mut dyn RngCore,
+    ) -> Radiance;
+}
+
+/// Unidirectional path tracer. Scattered directions are importance sampled
+/// with the density of the materials. `lights` are sampled at each bounce on a
+/// material having one, and combined with the emission found by scattering
+/// with multiple importance sampling.
+///
+/// Paths stop after `max_depth` bounces, and may stop from `roulette_depth`
+/// on with a probability growing as their throughput decreases, the surviving
+/// ones being reweighted accordingly.
+pub struct PathIntegrator {
+    pub max_depth: u32,
+    pub roulette_depth: u32,
+}
+
+/// Highest probability for a path to survive the Russian roulette, which
+/// keeps paths trapped between perfect reflectors from going on forever.
+const MAX_SURVIVAL_PROBABILITY: f64 = 0.95;
+
+fn get_survival_probability(throughput: &Vector3) -> f64 {
+    throughput
+        .x
+        .max(throughput.y)
+        .max(throughput.z)
+        .min(MAX_SURVIVAL_PROBABILITY)
+}
+
+impl Integrator for PathIntegrator {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+        background: &Background,
+        rng: &mut dyn RngCore,
+    ) -> Radiance {
+        let mut radiance = Radiance::default();
+        let mut throughput = Vector3::from((1., 1., 1.));
+        let mut ray = *ray;
+        let mut emission_weight = 1.;
+
+        for depth in 0..self.max_depth {
+            let hit = match world.sample_hit(&ray, SURFACE_OFFSET, f64::MAX, rng) {
+                Some(hit) => hit,
+                None => {
+                    radiance += background.radiance(&ray) * throughput;
+                    break;
+                }
+            };
+            radiance += hit.material.emitted(&hit) * (throughput * emission_weight);
+
+            let is_last_bounce = depth + 1 == self.max_depth;
+            match hit.material.scattering_pdf(&hit) {
+                Some(scattering_pdf) => {
+                    // Lights found by the last scattered ray would be ignored.
+                    if !is_last_bounce {
+                        let direct =
+                            sample_lights(&ray, &hit, scattering_pdf.as_ref(), world, lights, rng);
+                        radiance += direct * throughput;
+                    }
+
+                    let direction = match scattering_pdf.generate(rng) {
+                        Some(direction) => direction,
+                        None => break,
+                    };
+                    let density = scattering_pdf.value(&direction);
+                    if density <= 0. {
+                        break;
+                    }
+                    let bsdf = hit.material.bsdf(&ray, &hit, &direction);
+                    throughput = throughput.component_mul(&(bsdf / density));
+                    let light_density = HittablePdf::new(lights, hit.point).value(&direction);
+                    emission_weight = power_heuristic(density, light_density);
+                    ray = Ray::with_time(hit.point, direction, ray.time);
+                }
+                None => match hit.material.scatter(&ray, &hit, rng) {
+                    Some((scattered, attenuation)) => {
+                        throughput = throughput.component_mul(&attenuation);
+                        emission_weight = 1.;
+                        ray = scattered;
+                    }
+                    None => break,
+                },
+            }
+
+            if depth + 1 >= self.roulette_depth {
+                let survival_probability = get_survival_probability(&throughput);
+                if rng.gen_range(0., 1.) >= survival_probability {
+                    break;
+                }
+                throughput /= survival_probability;
+            }
+        }
+
+        radiance
+    }
+}
+
+/// Shows the normals of the surfaces seen by the camera, mapped to [0, 1].
+pub struct NormalsIntegrator;
+
+impl Integrator for NormalsIntegrator {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        world: &dyn Hittable,
+        _lights: &dyn Hittable,
+        _background: &Background,
+        rng: &mut dyn RngCore,
+    ) -> Radiance {
+        match world.sample_hit(ray, SURFACE_OFFSET, f64::MAX, rng) {
+            Some(hit) => {
+                let color = (hit.normal.normalized() + Vector3::from((1., 1., 1.))) * 0.5;
+                Radiance::new(color.x, color.y, color.z)
+            }
+            None => Radiance::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aabb::Aabb;
+    use crate::hit::{FlipNormals, HitRecord, HittableList, Sphere, SurfaceSample, XZRect};
+    use crate::material::{DiffuseLight, Lambertian, Metal};
+    use crate::random::Pcg32;
+    use crate::texture::ConstantTexture;
+    use std::sync::Arc;
+
+    /// A diffuse floor of albedo 0.5 lit by a sphere of radiance 4 seen under
+    /// a cone of half angle 30°, which reflects `0.5 * 4 * sin²(30°)`.
+    const EXPECTED_FLOOR_RADIANCE: f64 = 0.5;
+
+    fn get_lit_floor() -> (HittableList, Arc<dyn Hittable>) {
+        let light: Arc<dyn Hittable> = Arc::new(Sphere::new(
+            Vector3::from((0., 2., 0.)),
+            1.,
+            Box::new(DiffuseLight {
+                emit: Radiance::new(4., 4., 4.),
+            }),
+        ));
+        let floor = XZRect::new(
+            (-100., 100.),
+            (-100., 100.),
+            0.,
+            Box::new(Lambertian::new(ConstantTexture::new(Vector3::from((
+                0.5, 0.5, 0.5,
+            ))))),
+        );
+
+        (
+            HittableList::new(vec![Box::new(floor), Box::new(light.clone())]),
+            light,
+        )
+    }
+
+    fn get_floor_ray() -> Ray {
+        Ray::new(Vector3::from((3., 1., 0.)), Vector3::from((-3., -1., 0.)))
+    }
+
+    fn get_mean_floor_radiance(
+        integrator: &dyn Integrator,
+        lights: &dyn Hittable,
+        sample_count: u32,
+    ) -> f64 {
+        let mut rng = Pcg32::new(0, 0);
+        let (world, _) = get_lit_floor();
+        let background = Background::Uniform(Radiance::default());
+
+        let sum: f64 = (0..sample_count)
+            .map(|_| {
+                integrator
+                    .radiance(&get_floor_ray(), &world, lights, &background, &mut rng)
+                    .r
+            })
+            .sum();
+        sum / sample_count as f64
+    }
+
+    fn get_light_list() -> HittableList {
+        let (_, light) = get_lit_floor();
+        HittableList::new(vec![Box::new(light)])
+    }
+
+    const TWO_BOUNCES: PathIntegrator = PathIntegrator {
+        max_depth: 2,
+        roulette_depth: u32::MAX,
+    };
+
+    #[test]
+    fn power_heuristic_weights_sum_to_one() {
+        let weight = power_heuristic(0.3, 1.2);
+
+        assert!((weight + power_heuristic(1.2, 0.3) - 1.).abs() < 1e-12);
+        assert!(weight < 0.5);
+        assert_eq!(1., power_heuristic(0.3, 0.));
+    }
+
+    #[test]
+    fn sampling_lights_converges_to_the_reflected_radiance() {
+        let mean = get_mean_floor_radiance(&TWO_BOUNCES, &get_light_list(), 4000);
+
+        assert!((mean - EXPECTED_FLOOR_RADIANCE).abs() < 0.02);
+    }
+
+    #[test]
+    fn scattering_alone_converges_to_the_same_radiance() {
+        let lights = HittableList::new(Vec::new());
+
+        let mean = get_mean_floor_radiance(&TWO_BOUNCES, &lights, 40000);
+
+        assert!((mean - EXPECTED_FLOOR_RADIANCE).abs() < 0.02);
+    }
+
+    #[test]
+    fn russian_roulette_keeps_the_same_radiance() {
+        let integrator = PathIntegrator {
+            max_depth: 2,
+            roulette_depth: 0,
+        };
+
+        let mean = get_mean_floor_radiance(&integrator, &get_light_list(), 8000);
+
+        assert!((mean - EXPECTED_FLOOR_RADIANCE).abs() < 0.02);
+    }
+
+    /// A light whose density is zero where it was sampled, as can happen at
+    /// grazing angles.
+    struct MissedLight(Arc<dyn Hittable>);
+
+    impl Hittable for MissedLight {
+        fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+            self.0.hit(ray, t_min, t_max)
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            self.0.bounding_box()
+        }
+
+        fn sample_surface(&self, origin: &Vector3, rng: &mut dyn RngCore) -> Option<SurfaceSample> {
+            self.0.sample_surface(origin, rng)
+        }
+    }
+
+    #[test]
+    fn ignores_light_samples_without_density() {
+        let (_, light) = get_lit_floor();
+        let lights = MissedLight(light);
+
+        let mean = get_mean_floor_radiance(&TWO_BOUNCES, &lights, 100);
+
+        assert!(mean.is_finite());
+    }
+
+    #[test]
+    fn stops_at_depth_limit() {
+        let mut rng = Pcg32::new(0, 0);
+        let (world, light) = get_lit_floor();
+        let lights = HittableList::new(vec![Box::new(light)]);
+        let background = Background::Uniform(Radiance::new(1., 1., 1.));
+        let integrator = PathIntegrator {
+            max_depth: 1,
+            roulette_depth: u32::MAX,
+        };
+
+        assert_eq!(
+            Radiance::default(),
+            integrator.radiance(&get_floor_ray(), &world, &lights, &background, &mut rng)
+        );
+    }
+
+    #[test]
+    fn follows_long_paths_without_recursion() {
+        let mut rng = Pcg32::new(0, 0);
+        let mirror = FlipNormals::new(Box::new(Sphere::new(
+            Vector3::default(),
+            1.,
+            Box::new(Metal::new(
+                ConstantTexture::new(Vector3::from((1., 1., 1.))),
+                0.,
+            )),
+        )));
+        let background = Background::Uniform(Radiance::new(1., 1., 1.));
+        let integrator = PathIntegrator {
+            max_depth: 100_000,
+            roulette_depth: u32::MAX,
+        };
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., 1.)));
+
+        let radiance = integrator.radiance(
+            &ray,
+            &mirror,
+            &HittableList::new(Vec::new()),
+            &background,
+            &mut rng,
+        );
+
+        assert_eq!(Radiance::default(), radiance);
+    }
+
+    #[test]
+    fn normals_integrator_maps_normals_to_colors() {
+        let mut rng = Pcg32::new(0, 0);
+        let sphere = Sphere::new(
+            Vector3::from((0., 0., -2.)),
+            1.,
+            Box::new(Lambertian::new(ConstantTexture::new(Vector3::default()))),
+        );
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+        let background = Background::Uniform(Radiance::default());
+
+        let radiance = NormalsIntegrator.radiance(
+            &ray,
+            &sphere,
+            &HittableList::new(Vec::new()),
+            &background,
+            &mut rng,
+        );
+
+        assert_eq!(Radiance::new(0.5, 0.5, 1.), radiance);
+    }
+}
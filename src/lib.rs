@@ -1,8 +1,29 @@
+pub mod aabb;
+pub mod adaptive;
+pub mod bvh;
 pub mod camera;
+pub mod checkpoint;
 pub mod color;
+pub mod exr;
+pub mod hdr;
 pub mod hit;
+pub mod integrator;
 pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod obj;
+pub mod pdf;
+pub mod perlin;
+pub mod png;
 pub mod ppm;
+pub mod progressive;
+pub mod radiance;
+pub mod random;
 pub mod ray;
+pub mod render;
+pub mod sampler;
+pub mod scene_file;
 pub mod scenes;
+pub mod texture;
+pub mod transform;
 pub mod vector3;
@@ -1,113 +1,346 @@
-mod camera;
-mod color;
-mod hit;
-mod material;
-mod ppm;
-mod ray;
-mod scenes;
-mod vector3;
-
-use camera::Camera;
-use color::Color;
-use hit::{Hittable, HittableList};
-use rand::Rng;
-use ray::Ray;
-use scenes::{get_scene_1, get_scene_2};
+mod options;
+
+use clap::Parser;
+use options::{
+    find_changed_setting, guess_format, ExrCompression, ExrPixelType, IntegratorChoice, Options,
+    OutputFormat, SamplerChoice, SceneChoice,
+};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use std::f64;
-use vector3::Vector3;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use weekend_raytracer::adaptive::{AdaptiveSampling, PixelStatistics};
+use weekend_raytracer::bvh::BvhNode;
+use weekend_raytracer::checkpoint::Checkpoint;
+use weekend_raytracer::color::Color;
+use weekend_raytracer::exr;
+use weekend_raytracer::hdr;
+use weekend_raytracer::hit::Hittable;
+use weekend_raytracer::integrator::{Integrator, NormalsIntegrator, PathIntegrator};
+use weekend_raytracer::medium::Atmosphere;
+use weekend_raytracer::png;
+use weekend_raytracer::ppm;
+use weekend_raytracer::progressive;
+use weekend_raytracer::radiance::Radiance;
+use weekend_raytracer::render::{self, Framebuffer};
+use weekend_raytracer::sampler::{
+    HaltonSampler, IndependentSampler, Sampler, SamplerRng, SobolSampler, StratifiedSampler,
+};
+use weekend_raytracer::scene_file::{self, SceneError};
+use weekend_raytracer::scenes::{get_cornell_box, get_scene_1, get_scene_2, Scene};
+use weekend_raytracer::vector3::Vector3;
+
+fn simple_gamma_correction(radiance: Radiance) -> Color {
+    Color::from(radiance.gamma_corrected())
+}
 
-const MAX_DEPTH_LIMIT: u32 = 50;
+fn get_exr_settings(options: &Options) -> (exr::PixelType, exr::Compression) {
+    let pixel_type = match options.exr_pixel_type {
+        ExrPixelType::Half => exr::PixelType::Half,
+        ExrPixelType::Float => exr::PixelType::Float,
+    };
+    let compression = match options.exr_compression {
+        ExrCompression::None => exr::Compression::None,
+        ExrCompression::Zip => exr::Compression::Zip,
+    };
+    (pixel_type, compression)
+}
 
-fn color(ray: Ray, world: &HittableList, depth_limit: u32) -> Color {
-    if depth_limit >= MAX_DEPTH_LIMIT {
-        return Color::default();
+fn get_scene(options: &Options, seed: u64) -> Result<Scene, SceneError> {
+    let geometry = (options.width, options.height);
+    match &options.scene {
+        SceneChoice::Scene1 => Ok(get_scene_1(geometry)),
+        SceneChoice::Scene2 => Ok(get_scene_2(geometry, &mut StdRng::seed_from_u64(seed))),
+        SceneChoice::CornellBox => Ok(get_cornell_box(geometry)),
+        SceneChoice::File(path) => scene_file::load_scene(path, geometry),
     }
+}
 
-    let hit_point = world.hit(&ray, 0.001, f64::MAX);
-    match hit_point {
-        Some(hit) => {
-            if let Some((reflection_ray, attenuation)) = hit.material.scatter(&ray, &hit) {
-                let (r, g, b): (f64, f64, f64) =
-                    color(reflection_ray, world, depth_limit + 1).into();
-                Color::from((
-                    attenuation.x * r / 255.,
-                    attenuation.y * g / 255.,
-                    attenuation.z * b / 255.,
-                ))
-            } else {
-                Color::default()
-            }
+fn get_integrator(options: &Options) -> Box<dyn Integrator> {
+    match options.integrator {
+        IntegratorChoice::Path => Box::new(PathIntegrator {
+            max_depth: options.max_depth,
+            roulette_depth: options.roulette_depth,
+        }),
+        IntegratorChoice::Normals => Box::new(NormalsIntegrator),
+    }
+}
+
+fn get_sampler(options: &Options, seed: u64) -> Box<dyn Sampler> {
+    match options.sampler {
+        SamplerChoice::Independent => Box::new(IndependentSampler::new(seed)),
+        SamplerChoice::Stratified => {
+            Box::new(StratifiedSampler::new(seed, options.get_stratum_count()))
         }
-        None => {
-            let normalized_direction = ray.point_at_parameter(1.).normalized();
-            let t = (0.5 * (normalized_direction.y + 1.)) as f32;
+        SamplerChoice::Halton => Box::new(HaltonSampler::new(seed)),
+        SamplerChoice::Sobol => Box::new(SobolSampler::new(seed)),
+    }
+}
 
-            let white = Color::new(255, 255, 255);
-            let blue = Color::new(127, 178, 255);
+fn get_adaptive_sampling(options: &Options) -> AdaptiveSampling {
+    match options.adaptive_threshold {
+        Some(threshold) => AdaptiveSampling {
+            min_samples: options.min_samples,
+            max_samples: options.samples,
+            threshold,
+        },
+        None => AdaptiveSampling::fixed(options.samples),
+    }
+}
 
-            white.lerp(blue, t)
+fn get_image_content(
+    options: &Options,
+    format: OutputFormat,
+    image: &Framebuffer<Radiance>,
+) -> Vec<u8> {
+    let (width, height) = (image.width, image.height);
+    let to_color = |x, y| simple_gamma_correction(image.get(x, y));
+    let to_radiance = |x, y| image.get(x, y);
+    match format {
+        OutputFormat::Ppm => ppm::get_file_content(width, height, to_color).into_bytes(),
+        OutputFormat::PpmBinary => ppm::get_binary_file_content(width, height, to_color),
+        OutputFormat::Pfm => ppm::get_pfm_file_content(width, height, to_radiance),
+        OutputFormat::Png => png::get_file_content(width, height, to_color),
+        OutputFormat::Exr => {
+            let (pixel_type, compression) = get_exr_settings(options);
+            exr::get_file_content(width, height, to_radiance, pixel_type, compression)
         }
+        OutputFormat::Hdr => hdr::get_file_content(width, height, to_radiance),
     }
 }
 
-fn simple_gamma_on_component(component: f64) -> f64 {
-    assert!(component <= 255.);
-    (component / 255.).sqrt()
+/// Grey levels giving the number of samples of each pixel relative to
+/// `max_samples`.
+fn get_sample_count_map(
+    statistics: &Framebuffer<PixelStatistics>,
+    max_samples: u32,
+) -> Framebuffer<Radiance> {
+    let mut map = Framebuffer::new(statistics.width, statistics.height);
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let level = statistics.get(x, y).count as f64 / max_samples as f64;
+            map.set(x, y, Radiance::new(level, level, level));
+        }
+    }
+    map
 }
 
-fn simple_gamma_correction(color: Color) -> Color {
-    let color_as_tuple: (f64, f64, f64) = color.into();
-    let fixed_tuple = (
-        simple_gamma_on_component(color_as_tuple.0),
-        simple_gamma_on_component(color_as_tuple.1),
-        simple_gamma_on_component(color_as_tuple.2),
-    );
+/// Replaces the file at `path` at once, so that it is never seen half written.
+fn write_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut temporary_path = path.as_os_str().to_owned();
+    temporary_path.push(".tmp");
+    fs::write(&temporary_path, content)?;
+    fs::rename(&temporary_path, path)
+}
 
-    Color::from(fixed_tuple)
+fn write_output(options: &Options, content: &[u8]) -> io::Result<()> {
+    match &options.output {
+        Some(path) => write_file(path, content),
+        None => io::stdout().write_all(content),
+    }
 }
 
-enum Scene {
-    Scene1,
-    Scene2,
+/// Writes the mean of the samples taken so far, and their count when asked
+/// for.
+fn write_images(options: &Options, statistics: &Framebuffer<PixelStatistics>) {
+    if let Some(path) = &options.sample_count_map {
+        let map = get_sample_count_map(statistics, options.samples);
+        let content = get_image_content(options, guess_format(Some(path)), &map);
+        if let Err(error) = write_file(path, &content) {
+            eprintln!("Cannot write the sample count map: {}", error);
+            process::exit(1);
+        }
+    }
+
+    let image = progressive::get_image(statistics);
+    let output = get_image_content(options, options.output_format(), &image);
+    if let Err(error) = write_output(options, &output) {
+        eprintln!("Cannot write the image: {}", error);
+        process::exit(1);
+    }
 }
 
-fn get_scene(scene: Scene, geometry: (u32, u32)) -> (HittableList, Camera) {
-    match scene {
-        Scene::Scene1 => get_scene_1(geometry),
-        Scene::Scene2 => get_scene_2(geometry),
+fn write_checkpoint(options: &Options, checkpoint: &Checkpoint) {
+    if let Some(path) = &options.checkpoint {
+        if let Err(error) = write_file(path, &checkpoint.get_file_content()) {
+            eprintln!("Cannot write the checkpoint: {}", error);
+            process::exit(1);
+        }
     }
 }
 
+/// Loads the checkpoint to resume, making sure it was saved by a render with
+/// the same settings.
+fn load_checkpoint(path: &Path, options: &Options, settings: &toml::Table) -> Checkpoint {
+    let checkpoint = Checkpoint::load(path).unwrap_or_else(|error| {
+        eprintln!("Cannot load the checkpoint: {}", error);
+        process::exit(1);
+    });
+    let saved_settings: toml::Table =
+        toml::from_str(&checkpoint.settings).unwrap_or_else(|error| {
+            eprintln!("Cannot read the settings of the checkpoint: {}", error);
+            process::exit(1);
+        });
+
+    if let Some(setting) = find_changed_setting(settings, &saved_settings) {
+        eprintln!(
+            "Cannot resume the render: its {} setting is different",
+            setting
+        );
+        process::exit(1);
+    }
+    let statistics = &checkpoint.statistics;
+    if (statistics.width, statistics.height) != (options.width, options.height) {
+        eprintln!(
+            "Cannot resume the render: its image is {}x{}",
+            statistics.width, statistics.height
+        );
+        process::exit(1);
+    }
+    if options.seed.is_some_and(|seed| seed != checkpoint.seed) {
+        eprintln!("Cannot resume the render: its seed is different");
+        process::exit(1);
+    }
+    checkpoint
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
 fn main() {
-    let width = 800;
-    let height = 400;
-    let sub_sample_count = 100;
+    let options = Options::parse();
+    let width = options.width;
+    let height = options.height;
+    let thread_count = options
+        .threads
+        .map_or_else(render::available_threads, |threads| threads as usize);
+    let settings = options.get_render_settings().unwrap_or_else(|error| {
+        eprintln!("Cannot read the scene: {}", error);
+        process::exit(1);
+    });
+    let resumed = options
+        .resume
+        .as_deref()
+        .map(|path| load_checkpoint(path, &options, &settings));
+    let seed = resumed
+        .as_ref()
+        .map(|checkpoint| checkpoint.seed)
+        .or(options.seed)
+        .unwrap_or_else(rand::random);
 
-    let (world, camera) = get_scene(Scene::Scene2, (width, height));
+    let Scene {
+        world,
+        lights,
+        camera,
+        background,
+        atmosphere,
+    } = get_scene(&options, seed).unwrap_or_else(|error| {
+        eprintln!("Cannot load the scene: {}", error);
+        process::exit(1);
+    });
+    // A BVH needs objects, but scenes with only a camera show the background.
+    let world: Box<dyn Hittable> = if world.is_empty() {
+        Box::new(world)
+    } else {
+        Box::new(BvhNode::new(world))
+    };
+    let atmosphere = options
+        .atmosphere
+        .map(|density| Atmosphere {
+            density,
+            albedo: Vector3::from((1., 1., 1.)),
+        })
+        .or(atmosphere);
+    let world = match atmosphere {
+        Some(atmosphere) => Box::new(atmosphere.wrap(world, camera.origin())),
+        None => world,
+    };
 
-    let output = ppm::get_file_content(width, height, |x: u32, y: u32| -> Color {
-        let mut rng = rand::thread_rng();
-        let mut color_accumulator = Vector3::default();
+    let integrator = get_integrator(&options);
+    let adaptive_sampling = get_adaptive_sampling(&options);
 
-        for _i in 0..sub_sample_count {
-            let u = (x as f64 + rng.gen_range(0., 1.)) / width as f64;
-            let v = (y as f64 + rng.gen_range(0., 1.)) / height as f64;
+    // Each pixel creates its sampler once per pass, and moves it into the
+    // function taking its samples, which borrows the rest of the scene.
+    let (camera, world, lights, background) = (&camera, &world, &lights, &background);
+    let integrator = integrator.as_ref();
+    let pixel_sampler = |x, y| {
+        let mut sampler = get_sampler(&options, seed);
+        move |index| {
+            sampler.start_pixel_sample(x, y, index);
+            let (offset_x, offset_y) = sampler.get_2d();
+            let u = (x as f64 + offset_x) / width as f64;
+            let v = (y as f64 + offset_y) / height as f64;
 
-            let ray = camera.get_ray(u, v);
-            let color = color(ray, &world, 0);
-            let color_as_tuple: (f64, f64, f64) = color.into();
-            color_accumulator += Vector3::from(color_as_tuple);
+            let mut rng = SamplerRng::new(sampler.as_mut());
+            let ray = camera.get_ray(u, v, &mut rng);
+            integrator.radiance(&ray, world.as_ref(), lights, background, &mut rng)
         }
+    };
 
-        color_accumulator /= sub_sample_count as f64;
-        color_accumulator /= 255.;
+    if options.checkpoint.is_some() {
+        if let Err(error) = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::Relaxed)) {
+            eprintln!("Cannot handle interruptions: {}", error);
+            process::exit(1);
+        }
+    }
 
-        simple_gamma_correction(Color::from((
-            color_accumulator.x,
-            color_accumulator.y,
-            color_accumulator.z,
-        )))
-    });
+    let pass_samples = match (options.pass_samples, options.checkpoint_interval) {
+        (Some(pass_samples), _) => pass_samples,
+        (None, Some(_)) => 1,
+        (None, None) => options.samples,
+    };
+    let mut checkpoint = Checkpoint {
+        settings: settings.to_string(),
+        seed,
+        statistics: resumed.map_or_else(
+            || Framebuffer::new(width, height),
+            |checkpoint| checkpoint.statistics,
+        ),
+    };
+    let mut last_snapshot = Instant::now();
+    let mut last_checkpoint = Instant::now();
+    loop {
+        checkpoint.statistics = progressive::render_pass(
+            &checkpoint.statistics,
+            thread_count,
+            pass_samples,
+            &adaptive_sampling,
+            pixel_sampler,
+            &INTERRUPTED,
+        );
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            write_checkpoint(&options, &checkpoint);
+            if options.output.is_some() {
+                write_images(&options, &checkpoint.statistics);
+            }
+            eprintln!("Interrupted, the render can be resumed from its checkpoint");
+            process::exit(130);
+        }
+        if progressive::is_complete(&checkpoint.statistics, &adaptive_sampling) {
+            break;
+        }
+
+        let is_checkpoint_due = options
+            .checkpoint_interval
+            .is_some_and(|interval| last_checkpoint.elapsed().as_secs_f64() >= interval);
+        if is_checkpoint_due {
+            write_checkpoint(&options, &checkpoint);
+            last_checkpoint = Instant::now();
+        }
+
+        let is_snapshot_due = options
+            .snapshot_interval
+            .is_none_or(|interval| last_snapshot.elapsed().as_secs_f64() >= interval);
+        if options.pass_samples.is_some() && options.output.is_some() && is_snapshot_due {
+            write_images(&options, &checkpoint.statistics);
+            last_snapshot = Instant::now();
+        }
+    }
 
-    print!("{}", output);
+    write_checkpoint(&options, &checkpoint);
+    write_images(&options, &checkpoint.statistics);
 }
@@ -1,49 +1,101 @@
 use crate::hit::HitRecord;
+use crate::pdf::{CosinePdf, Pdf, SpherePdf};
+use crate::radiance::Radiance;
 use crate::ray::Ray;
+use crate::texture::Texture;
 use crate::vector3::{random_in_unit_sphere, Vector3};
-use rand::Rng;
+use rand::{Rng, RngCore};
+use std::f64::consts;
+use std::sync::Arc;
 
-type Attenuation = Vector3;
+pub trait Material: Send + Sync {
+    /// Picks a scattered ray and its attenuation. By default, the direction
+    /// follows `scattering_pdf` and the attenuation is the BSDF over the
+    /// density.
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut dyn RngCore) -> Option<(Ray, Vector3)> {
+        let pdf = self.scattering_pdf(hit)?;
+        let direction = pdf.generate(rng)?;
+        let density = pdf.value(&direction);
+        if density <= 0. {
+            return None;
+        }
+
+        let attenuation = self.bsdf(ray, hit, &direction) / density;
+        Some((Ray::with_time(hit.point, direction, ray.time), attenuation))
+    }
+
+    fn emitted(&self, _hit: &HitRecord) -> Radiance {
+        Radiance::default()
+    }
 
-pub trait Material {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3)>;
+    /// Density of the scattered directions, which lets lights be sampled
+    /// explicitly. None for materials scattering in a single direction.
+    fn scattering_pdf(&self, _hit: &HitRecord) -> Option<Box<dyn Pdf>> {
+        None
+    }
+
+    /// Fraction of the light coming from `direction` which is scattered back
+    /// along `ray`, per unit of solid angle. It includes the cosine of the
+    /// incidence on surfaces.
+    fn bsdf(&self, _ray: &Ray, _hit: &HitRecord, _direction: &Vector3) -> Vector3 {
+        Vector3::default()
+    }
 }
 
 pub struct Lambertian {
-    pub albedo: Attenuation,
+    pub albedo: Arc<dyn Texture>,
+}
+
+impl Lambertian {
+    pub fn new<T: Texture + 'static>(albedo: T) -> Self {
+        Lambertian {
+            albedo: Arc::new(albedo),
+        }
+    }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3)> {
-        let diffuse_direction = hit.point + hit.normal + random_in_unit_sphere();
-        let diffuse_ray = Ray::new(hit.point, diffuse_direction - hit.point);
+    fn scattering_pdf(&self, hit: &HitRecord) -> Option<Box<dyn Pdf>> {
+        Some(Box::new(CosinePdf::new(&hit.normal)))
+    }
 
-        Some((diffuse_ray, self.albedo))
+    fn bsdf(&self, _ray: &Ray, hit: &HitRecord, direction: &Vector3) -> Vector3 {
+        let cosine = hit.normal.dot(&direction.normalized());
+        self.albedo.value(hit.uv, &hit.point) * (cosine.max(0.) / consts::PI)
     }
 }
 
 pub struct Metal {
-    pub albedo: Attenuation,
+    pub albedo: Arc<dyn Texture>,
     pub fuzziness: f64,
 }
 
+impl Metal {
+    pub fn new<T: Texture + 'static>(albedo: T, fuzziness: f64) -> Self {
+        Metal {
+            albedo: Arc::new(albedo),
+            fuzziness,
+        }
+    }
+}
+
 fn reflect(incoming: Vector3, normal: Vector3) -> Vector3 {
     incoming - normal * 2. * incoming.dot(&normal)
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3)> {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut dyn RngCore) -> Option<(Ray, Vector3)> {
         let reflected = reflect(ray.direction.normalized(), hit.normal);
 
         if reflected.dot(&hit.normal) > 0. {
             let reflected_fuzziness = if self.fuzziness > 0. {
-                reflected + random_in_unit_sphere() * self.fuzziness.min(1.)
+                reflected + random_in_unit_sphere(rng) * self.fuzziness.min(1.)
             } else {
                 reflected
             };
-            let reflected_ray = Ray::new(hit.point, reflected_fuzziness);
+            let reflected_ray = Ray::with_time(hit.point, reflected_fuzziness, ray.time);
 
-            Some((reflected_ray, self.albedo))
+            Some((reflected_ray, self.albedo.value(hit.uv, &hit.point)))
         } else {
             None
         }
@@ -74,7 +126,7 @@ fn schlick(cosine: f64, refraction_index: f64) -> f64 {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3)> {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, rng: &mut dyn RngCore) -> Option<(Ray, Vector3)> {
         let reflected = reflect(ray.direction, hit.normal);
 
         let (outward_normal, ni_overnt, cosine) = if ray.direction.dot(&hit.normal) > 0. {
@@ -93,10 +145,195 @@ impl Material for Dielectric {
             };
 
         let attenuation = Vector3::from((1., 1., 1.));
-        if rand::thread_rng().gen_range(0., 1.) < reflection_probability {
-            Some((Ray::new(hit.point, reflected), attenuation))
+        if rng.gen_range(0., 1.) < reflection_probability {
+            Some((Ray::with_time(hit.point, reflected, ray.time), attenuation))
         } else {
-            Some((Ray::new(hit.point, refracted), attenuation))
+            Some((Ray::with_time(hit.point, refracted, ray.time), attenuation))
+        }
+    }
+}
+
+pub struct DiffuseLight {
+    pub emit: Radiance,
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _ray: &Ray,
+        _hit: &HitRecord,
+        _rng: &mut dyn RngCore,
+    ) -> Option<(Ray, Vector3)> {
+        None
+    }
+
+    fn emitted(&self, _hit: &HitRecord) -> Radiance {
+        self.emit
+    }
+}
+
+/// Phase function of participating media, scattering in all directions
+/// uniformly.
+pub struct Isotropic {
+    pub albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new<T: Texture + 'static>(albedo: T) -> Self {
+        Isotropic {
+            albedo: Arc::new(albedo),
+        }
+    }
+}
+
+impl Material for Isotropic {
+    fn scattering_pdf(&self, _hit: &HitRecord) -> Option<Box<dyn Pdf>> {
+        Some(Box::new(SpherePdf))
+    }
+
+    fn bsdf(&self, _ray: &Ray, hit: &HitRecord, _direction: &Vector3) -> Vector3 {
+        self.albedo.value(hit.uv, &hit.point) / (4. * consts::PI)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Pcg32;
+    use crate::render::Framebuffer;
+    use crate::texture::{ConstantTexture, ImageTexture};
+
+    fn get_hit(material: &dyn Material) -> HitRecord<'_> {
+        HitRecord {
+            t: 1.,
+            point: Vector3::from((0., 0., -1.)),
+            normal: Vector3::from((0., 0., 1.)),
+            uv: (0., 0.),
+            material,
         }
     }
+
+    #[test]
+    fn lambertian_does_not_emit() {
+        let material = Lambertian::new(ConstantTexture::new(Vector3::from((1., 1., 1.))));
+
+        assert_eq!(Radiance::default(), material.emitted(&get_hit(&material)));
+    }
+
+    #[test]
+    fn scattered_rays_keep_the_time_of_incoming_rays() {
+        let mut rng = Pcg32::new(0, 0);
+        let materials: Vec<Box<dyn Material>> = vec![
+            Box::new(Lambertian::new(ConstantTexture::new(Vector3::default()))),
+            Box::new(Metal::new(ConstantTexture::new(Vector3::default()), 0.)),
+            Box::new(Dielectric {
+                refraction_index: 1.5,
+            }),
+        ];
+        let ray = Ray::with_time(Vector3::default(), Vector3::from((0., 0., -1.)), 0.25);
+
+        for material in &materials {
+            let (scattered, _) = material
+                .scatter(&ray, &get_hit(material.as_ref()), &mut rng)
+                .unwrap();
+            assert_eq!(0.25, scattered.time);
+        }
+    }
+
+    #[test]
+    fn isotropic_scatters_in_all_directions() {
+        let mut rng = Pcg32::new(0, 0);
+        let material = Isotropic::new(ConstantTexture::new(Vector3::from((0.5, 0.5, 0.5))));
+        let hit = get_hit(&material);
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+
+        let directions: Vec<Vector3> = (0..200)
+            .map(|_| material.scatter(&ray, &hit, &mut rng).unwrap().0.direction)
+            .collect();
+
+        for axis in 0..3 {
+            assert!(directions.iter().any(|direction| direction[axis] < 0.));
+            assert!(directions.iter().any(|direction| direction[axis] > 0.));
+        }
+    }
+
+    #[test]
+    fn lambertian_attenuation_comes_from_its_texture() {
+        let mut rng = Pcg32::new(0, 0);
+        let mut image = Framebuffer::new(2, 1);
+        image.set(1, 0, Vector3::from((0.5, 0.25, 1.)));
+        let material = Lambertian::new(ImageTexture::new(image));
+        let mut hit = get_hit(&material);
+        hit.uv = (0.75, 0.5);
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+
+        let (_, attenuation) = material.scatter(&ray, &hit, &mut rng).unwrap();
+
+        assert_eq!(Vector3::from((0.5, 0.25, 1.)), attenuation);
+    }
+
+    #[test]
+    fn diffuse_light_emits_without_scattering() {
+        let mut rng = Pcg32::new(0, 0);
+        let material = DiffuseLight {
+            emit: Radiance::new(4., 4., 4.),
+        };
+        let hit = get_hit(&material);
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+
+        assert_eq!(Radiance::new(4., 4., 4.), material.emitted(&hit));
+        assert!(material.scatter(&ray, &hit, &mut rng).is_none());
+    }
+
+    #[test]
+    fn lambertian_scatters_around_the_normal_with_a_cosine_density() {
+        let mut rng = Pcg32::new(0, 0);
+        let material = Lambertian::new(ConstantTexture::new(Vector3::from((1., 1., 1.))));
+        let hit = get_hit(&material);
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+        let pdf = material.scattering_pdf(&hit).unwrap();
+
+        for _i in 0..100 {
+            let (scattered, _) = material.scatter(&ray, &hit, &mut rng).unwrap();
+            assert!(scattered.direction.dot(&hit.normal) >= 0.);
+        }
+        let straight_up = pdf.value(&Vector3::from((0., 0., 2.)));
+        let below = pdf.value(&Vector3::from((0., 1., -1.)));
+        assert!((straight_up - 1. / consts::PI).abs() < 1e-12);
+        assert_eq!(0., below);
+    }
+
+    #[test]
+    fn scattering_density_and_bsdf_give_the_attenuation() {
+        let mut rng = Pcg32::new(0, 0);
+        let albedo = Vector3::from((0.5, 0.25, 1.));
+        let materials: Vec<Box<dyn Material>> = vec![
+            Box::new(Lambertian::new(ConstantTexture::new(albedo))),
+            Box::new(Isotropic::new(ConstantTexture::new(albedo))),
+        ];
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+
+        for material in &materials {
+            let hit = get_hit(material.as_ref());
+            let pdf = material.scattering_pdf(&hit).unwrap();
+            for _i in 0..20 {
+                let (scattered, attenuation) = material.scatter(&ray, &hit, &mut rng).unwrap();
+                let bsdf = material.bsdf(&ray, &hit, &scattered.direction);
+                let expected = bsdf / pdf.value(&scattered.direction);
+                assert!((attenuation - expected).norm() < 1e-9);
+                assert!((attenuation - albedo).norm() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn specular_materials_cannot_be_sampled_towards_lights() {
+        let metal = Metal::new(ConstantTexture::new(Vector3::default()), 0.5);
+        let glass = Dielectric {
+            refraction_index: 1.5,
+        };
+
+        assert!(metal.scattering_pdf(&get_hit(&metal)).is_none());
+        assert!(glass.scattering_pdf(&get_hit(&glass)).is_none());
+    }
 }
@@ -0,0 +1,230 @@
+use crate::aabb::Aabb;
+use crate::hit::{HitRecord, Hittable, HittableList, Sphere};
+use crate::material::{Isotropic, Material};
+use crate::ray::Ray;
+use crate::texture::ConstantTexture;
+use crate::vector3::Vector3;
+use rand::{Rng, RngCore};
+use std::f64;
+
+const BOUNDARY_EPSILON: f64 = 0.0001;
+const ATMOSPHERE_MARGIN: f64 = 1.;
+
+/// A volume of uniform density filling a closed boundary. Rays traveling inside
+/// scatter after an exponentially distributed distance, drawn from the
+/// generator given to `sample_hit`. `hit`, without a generator, never finds
+/// the medium.
+pub struct ConstantMedium {
+    boundary: Box<dyn Hittable>,
+    negative_inverse_density: f64,
+    phase_function: Box<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Box<dyn Hittable>,
+        density: f64,
+        phase_function: Box<dyn Material>,
+    ) -> Self {
+        assert!(density > 0.);
+
+        ConstantMedium {
+            boundary,
+            negative_inverse_density: -1. / density,
+            phase_function,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, _ray: &Ray, _t_min: f64, _t_max: f64) -> Option<HitRecord<'_>> {
+        None
+    }
+
+    fn sample_hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        let entry = self.boundary.hit(ray, f64::MIN, f64::MAX)?;
+        let exit = self
+            .boundary
+            .hit(ray, entry.t + BOUNDARY_EPSILON, f64::MAX)?;
+
+        let entry_t = entry.t.max(t_min).max(0.);
+        let exit_t = exit.t.min(t_max);
+        if entry_t >= exit_t {
+            return None;
+        }
+
+        let ray_length = ray.direction.norm();
+        let distance_inside = (exit_t - entry_t) * ray_length;
+        // In (0, 1], so that the logarithm is finite.
+        let random: f64 = 1. - rng.gen_range(0., 1.);
+        let hit_distance = self.negative_inverse_density * random.ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = entry_t + hit_distance / ray_length;
+        Some(HitRecord {
+            t,
+            point: ray.point_at_parameter(t),
+            normal: Vector3::from((1., 0., 0.)),
+            uv: (0., 0.),
+            material: &(*self.phase_function),
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+/// Fog filling the whole scene.
+pub struct Atmosphere {
+    pub density: f64,
+    pub albedo: Vector3,
+}
+
+impl Atmosphere {
+    /// Surrounds `world` and the `viewpoint` with the smallest sphere of fog
+    /// containing them. Rays leaving it reach the background, so the density has
+    /// to be chosen according to the size of the scene.
+    pub fn wrap(&self, world: Box<dyn Hittable>, viewpoint: Vector3) -> HittableList {
+        let world_box = world
+            .bounding_box()
+            .unwrap_or_else(|| Aabb::new(viewpoint, viewpoint));
+        let bounds = world_box.surrounding(&Aabb::new(viewpoint, viewpoint));
+        let center = bounds.centroid();
+        let radius = (bounds.max - bounds.min).norm() / 2. + ATMOSPHERE_MARGIN;
+
+        let boundary = Sphere::new(
+            center,
+            radius,
+            Box::new(Isotropic::new(ConstantTexture::new(self.albedo))),
+        );
+        let medium = ConstantMedium::new(
+            Box::new(boundary),
+            self.density,
+            Box::new(Isotropic::new(ConstantTexture::new(self.albedo))),
+        );
+
+        HittableList::new(vec![world, Box::new(medium)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::random::Pcg32;
+
+    fn get_material() -> Box<dyn Material> {
+        Box::new(Isotropic::new(ConstantTexture::new(Vector3::from((
+            1., 1., 1.,
+        )))))
+    }
+
+    fn get_unit_sphere_medium(density: f64) -> ConstantMedium {
+        let boundary = Sphere::new(Vector3::default(), 1., get_material());
+        ConstantMedium::new(Box::new(boundary), density, get_material())
+    }
+
+    #[test]
+    fn dense_medium_scatters_right_after_its_boundary() {
+        let medium = get_unit_sphere_medium(1e9);
+        let ray = Ray::new(Vector3::from((0., 0., 5.)), Vector3::from((0., 0., -1.)));
+
+        let hit = medium
+            .sample_hit(&ray, 0., f64::MAX, &mut Pcg32::new(0, 0))
+            .unwrap();
+
+        assert!((hit.t - 4.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn thin_medium_lets_rays_through() {
+        let medium = get_unit_sphere_medium(1e-9);
+        let ray = Ray::new(Vector3::from((0., 0., 5.)), Vector3::from((0., 0., -1.)));
+
+        assert!(medium
+            .sample_hit(&ray, 0., f64::MAX, &mut Pcg32::new(0, 0))
+            .is_none());
+    }
+
+    #[test]
+    fn scatters_from_inside_its_boundary() {
+        let medium = get_unit_sphere_medium(1e9);
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+
+        let hit = medium
+            .sample_hit(&ray, 0.001, f64::MAX, &mut Pcg32::new(0, 0))
+            .unwrap();
+
+        assert!(hit.t < 0.01);
+    }
+
+    #[test]
+    fn does_not_scatter_beyond_t_max() {
+        let medium = get_unit_sphere_medium(1e9);
+        let ray = Ray::new(Vector3::from((0., 0., 5.)), Vector3::from((0., 0., -1.)));
+
+        assert!(medium
+            .sample_hit(&ray, 0., 3., &mut Pcg32::new(0, 0))
+            .is_none());
+    }
+
+    #[test]
+    fn is_not_found_without_a_generator() {
+        let medium = get_unit_sphere_medium(1e9);
+        let ray = Ray::new(Vector3::from((0., 0., 5.)), Vector3::from((0., 0., -1.)));
+
+        assert!(medium.hit(&ray, 0., f64::MAX).is_none());
+    }
+
+    #[test]
+    fn media_along_a_ray_scatter_independently() {
+        let get_medium = |z: f64| -> Box<dyn Hittable> {
+            let boundary = Sphere::new(Vector3::from((0., 0., z)), 1., get_material());
+            Box::new(ConstantMedium::new(
+                Box::new(boundary),
+                0.25,
+                get_material(),
+            ))
+        };
+        let media = HittableList::new(vec![get_medium(0.), get_medium(-3.)]);
+        let ray = Ray::new(Vector3::from((0., 0., 5.)), Vector3::from((0., 0., -1.)));
+        let mut rng = Pcg32::new(0, 0);
+
+        let ray_count = 20000;
+        let crossing_count = (0..ray_count)
+            .filter(|_| media.sample_hit(&ray, 0., f64::MAX, &mut rng).is_none())
+            .count();
+
+        // Each medium lets through exp(-0.25 * 2) of the rays.
+        let transmittance = crossing_count as f64 / ray_count as f64;
+        assert!((transmittance - (-1f64).exp()).abs() < 0.01);
+    }
+
+    #[test]
+    fn atmosphere_surrounds_world_and_viewpoint() {
+        let world = Sphere::new(
+            Vector3::default(),
+            1.,
+            Box::new(Lambertian::new(ConstantTexture::new(Vector3::default()))),
+        );
+        let atmosphere = Atmosphere {
+            density: 1e-9,
+            albedo: Vector3::from((1., 1., 1.)),
+        };
+
+        let wrapped = atmosphere.wrap(Box::new(world), Vector3::from((0., 0., 10.)));
+        let bounding_box = wrapped.bounding_box().unwrap();
+
+        assert!(bounding_box.max.z > 10.);
+        assert!(bounding_box.min.z < -1.);
+    }
+}
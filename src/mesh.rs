@@ -0,0 +1,416 @@
+use crate::aabb::Aabb;
+use crate::bvh::BvhNode;
+use crate::hit::{
+    uniform_area_pdf, HitRecord, Hittable, HittableList, SurfaceSample, SURFACE_OFFSET,
+};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vector3::Vector3;
+use rand::{Rng, RngCore};
+use std::sync::Arc;
+
+const EPSILON: f64 = 1e-9;
+
+/// Möller–Trumbore intersection. Returns the ray parameter and the barycentric
+/// coordinates of the hit relative to the second and third vertices.
+fn intersect(ray: &Ray, vertices: [Vector3; 3], t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+    let edge_1 = vertices[1] - vertices[0];
+    let edge_2 = vertices[2] - vertices[0];
+
+    let p = ray.direction.cross(&edge_2);
+    let determinant = edge_1.dot(&p);
+    if determinant.abs() < EPSILON {
+        return None;
+    }
+    let inverse_determinant = 1. / determinant;
+
+    let to_origin = ray.origin - vertices[0];
+    let b1 = to_origin.dot(&p) * inverse_determinant;
+    if !(0. ..=1.).contains(&b1) {
+        return None;
+    }
+
+    let q = to_origin.cross(&edge_1);
+    let b2 = ray.direction.dot(&q) * inverse_determinant;
+    if b2 < 0. || b1 + b2 > 1. {
+        return None;
+    }
+
+    let t = edge_2.dot(&q) * inverse_determinant;
+    if t_min < t && t < t_max {
+        Some((t, b1, b2))
+    } else {
+        None
+    }
+}
+
+fn bounding_box_of(vertices: [Vector3; 3]) -> Aabb {
+    Aabb::new(
+        vertices[0].min(&vertices[1]).min(&vertices[2]),
+        vertices[0].max(&vertices[1]).max(&vertices[2]),
+    )
+}
+
+fn geometric_normal(vertices: [Vector3; 3]) -> Vector3 {
+    (vertices[1] - vertices[0])
+        .cross(&(vertices[2] - vertices[0]))
+        .normalized()
+}
+
+fn area_of(vertices: [Vector3; 3]) -> f64 {
+    (vertices[1] - vertices[0])
+        .cross(&(vertices[2] - vertices[0]))
+        .norm()
+        / 2.
+}
+
+/// Picks a point uniformly on the triangle.
+fn sample_triangle(
+    vertices: [Vector3; 3],
+    origin: &Vector3,
+    rng: &mut dyn RngCore,
+) -> Option<SurfaceSample> {
+    let root: f64 = rng.gen_range(0., 1f64).sqrt();
+    let b2: f64 = rng.gen_range(0., 1.) * root;
+    let point = interpolate(vertices, root - b2, b2);
+    let normal = geometric_normal(vertices);
+
+    let pdf = uniform_area_pdf(area_of(vertices), origin, &point, &normal);
+    if pdf > 0. {
+        Some(SurfaceSample { point, normal, pdf })
+    } else {
+        None
+    }
+}
+
+fn get_triangle_pdf(vertices: [Vector3; 3], origin: &Vector3, direction: &Vector3) -> f64 {
+    let ray = Ray::new(*origin, *direction);
+    intersect(&ray, vertices, SURFACE_OFFSET, f64::MAX).map_or(0., |(t, _, _)| {
+        let point = ray.point_at_parameter(t);
+        uniform_area_pdf(
+            area_of(vertices),
+            origin,
+            &point,
+            &geometric_normal(vertices),
+        )
+    })
+}
+
+fn interpolate(values: [Vector3; 3], b1: f64, b2: f64) -> Vector3 {
+    values[0] * (1. - b1 - b2) + values[1] * b1 + values[2] * b2
+}
+
+pub struct Triangle {
+    vertices: [Vector3; 3],
+    material: Box<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(a: Vector3, b: Vector3, c: Vector3, material: Box<dyn Material>) -> Self {
+        Triangle {
+            vertices: [a, b, c],
+            material,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t, b1, b2) = intersect(ray, self.vertices, t_min, t_max)?;
+
+        Some(HitRecord {
+            t,
+            point: ray.point_at_parameter(t),
+            normal: geometric_normal(self.vertices),
+            uv: (b1, b2),
+            material: &(*self.material),
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(bounding_box_of(self.vertices))
+    }
+
+    fn sample_surface(&self, origin: &Vector3, rng: &mut dyn RngCore) -> Option<SurfaceSample> {
+        sample_triangle(self.vertices, origin, rng)
+    }
+
+    fn surface_pdf(&self, origin: &Vector3, direction: &Vector3) -> f64 {
+        get_triangle_pdf(self.vertices, origin, direction)
+    }
+}
+
+/// Indices of a face in the buffers of a `TriangleMesh`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MeshFace {
+    pub vertices: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub texcoords: Option<[usize; 3]>,
+}
+
+struct MeshData {
+    positions: Vec<Vector3>,
+    normals: Vec<Vector3>,
+    texcoords: Vec<(f64, f64)>,
+    faces: Vec<MeshFace>,
+    material: Box<dyn Material>,
+}
+
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    face: usize,
+}
+
+impl MeshTriangle {
+    fn vertices(&self) -> [Vector3; 3] {
+        let indices = self.mesh.faces[self.face].vertices;
+        [
+            self.mesh.positions[indices[0]],
+            self.mesh.positions[indices[1]],
+            self.mesh.positions[indices[2]],
+        ]
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let vertices = self.vertices();
+        let (t, b1, b2) = intersect(ray, vertices, t_min, t_max)?;
+        let face = &self.mesh.faces[self.face];
+
+        let normal = match face.normals {
+            Some(indices) => {
+                let normals = [
+                    self.mesh.normals[indices[0]],
+                    self.mesh.normals[indices[1]],
+                    self.mesh.normals[indices[2]],
+                ];
+                let normal = interpolate(normals, b1, b2);
+                // Zero normals, which some exporters write, give no direction.
+                if normal.squared_norm() > 0. {
+                    normal.normalized()
+                } else {
+                    geometric_normal(vertices)
+                }
+            }
+            None => geometric_normal(vertices),
+        };
+
+        let uv = match face.texcoords {
+            Some(indices) => {
+                let (u0, v0) = self.mesh.texcoords[indices[0]];
+                let (u1, v1) = self.mesh.texcoords[indices[1]];
+                let (u2, v2) = self.mesh.texcoords[indices[2]];
+                let b0 = 1. - b1 - b2;
+                (b0 * u0 + b1 * u1 + b2 * u2, b0 * v0 + b1 * v1 + b2 * v2)
+            }
+            None => (b1, b2),
+        };
+
+        Some(HitRecord {
+            t,
+            point: ray.point_at_parameter(t),
+            normal,
+            uv,
+            material: &(*self.mesh.material),
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(bounding_box_of(self.vertices()))
+    }
+
+    fn sample_surface(&self, origin: &Vector3, rng: &mut dyn RngCore) -> Option<SurfaceSample> {
+        sample_triangle(self.vertices(), origin, rng)
+    }
+
+    fn surface_pdf(&self, origin: &Vector3, direction: &Vector3) -> f64 {
+        get_triangle_pdf(self.vertices(), origin, direction)
+    }
+}
+
+/// Triangles sharing vertex, normal and texture coordinate buffers, along with
+/// one material. Faces are kept in their own bounding volume hierarchy.
+pub struct TriangleMesh {
+    triangles: BvhNode,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Vector3>,
+        normals: Vec<Vector3>,
+        texcoords: Vec<(f64, f64)>,
+        faces: Vec<MeshFace>,
+        material: Box<dyn Material>,
+    ) -> Self {
+        let face_count = faces.len();
+        let mesh = Arc::new(MeshData {
+            positions,
+            normals,
+            texcoords,
+            faces,
+            material,
+        });
+
+        let triangles: Vec<Box<dyn Hittable>> = (0..face_count)
+            .map(|face| -> Box<dyn Hittable> {
+                Box::new(MeshTriangle {
+                    mesh: Arc::clone(&mesh),
+                    face,
+                })
+            })
+            .collect();
+
+        TriangleMesh {
+            triangles: BvhNode::new(HittableList::new(triangles)),
+        }
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.triangles.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.triangles.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::random::Pcg32;
+    use crate::texture::ConstantTexture;
+
+    fn get_dummy_material() -> Box<dyn Material> {
+        Box::new(Lambertian::new(ConstantTexture::new(Vector3::default())))
+    }
+
+    fn get_triangle() -> Triangle {
+        Triangle::new(
+            Vector3::from((-1., -1., -2.)),
+            Vector3::from((1., -1., -2.)),
+            Vector3::from((0., 1., -2.)),
+            get_dummy_material(),
+        )
+    }
+
+    fn get_square_mesh(normals: Option<[usize; 3]>) -> TriangleMesh {
+        TriangleMesh::new(
+            vec![
+                Vector3::from((-1., -1., 0.)),
+                Vector3::from((1., -1., 0.)),
+                Vector3::from((1., 1., 0.)),
+                Vector3::from((-1., 1., 0.)),
+            ],
+            vec![
+                Vector3::from((0., 0., 1.)),
+                Vector3::from((1., 0., 0.)),
+                Vector3::default(),
+            ],
+            vec![(0., 0.), (1., 0.), (1., 1.), (0., 1.)],
+            vec![
+                MeshFace {
+                    vertices: [0, 1, 2],
+                    normals,
+                    texcoords: Some([0, 1, 2]),
+                },
+                MeshFace {
+                    vertices: [0, 2, 3],
+                    normals,
+                    texcoords: Some([0, 2, 3]),
+                },
+            ],
+            get_dummy_material(),
+        )
+    }
+
+    #[test]
+    fn hits_triangle_from_origin() {
+        let triangle = get_triangle();
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+
+        let hit = triangle.hit(&ray, 0., 10.).unwrap();
+
+        assert_eq!(Vector3::from((0., 0., -2.)), hit.point);
+        assert_eq!(Vector3::from((0., 0., 1.)), hit.normal);
+    }
+
+    #[test]
+    fn misses_triangle_on_the_side() {
+        let ray = Ray::new(Vector3::from((2., 0., 0.)), Vector3::from((0., 0., -1.)));
+
+        assert!(get_triangle().hit(&ray, 0., 10.).is_none());
+    }
+
+    #[test]
+    fn misses_triangle_parallel_to_the_ray() {
+        let ray = Ray::new(Vector3::from((0., 0., -2.)), Vector3::from((1., 0., 0.)));
+
+        assert!(get_triangle().hit(&ray, -10., 10.).is_none());
+    }
+
+    #[test]
+    fn misses_triangle_out_of_range() {
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+
+        assert!(get_triangle().hit(&ray, 0., 1.).is_none());
+    }
+
+    #[test]
+    fn hits_mesh_faces_with_interpolated_texture_coordinates() {
+        let mesh = get_square_mesh(None);
+        let ray = Ray::new(Vector3::from((-0.5, 0.5, 1.)), Vector3::from((0., 0., -1.)));
+
+        let hit = mesh.hit(&ray, 0., 10.).unwrap();
+
+        assert_eq!(Vector3::from((-0.5, 0.5, 0.)), hit.point);
+        assert_eq!(Vector3::from((0., 0., 1.)), hit.normal);
+        assert_eq!((0.25, 0.75), hit.uv);
+    }
+
+    #[test]
+    fn mesh_interpolates_shading_normals() {
+        let mesh = get_square_mesh(Some([0, 1, 1]));
+        let ray = Ray::new(Vector3::from((0.5, -0.5, 1.)), Vector3::from((0., 0., -1.)));
+
+        let hit = mesh.hit(&ray, 0., 10.).unwrap();
+
+        let expected = Vector3::from((0.75, 0., 0.25)).normalized();
+        assert!((expected - hit.normal).norm() < 1e-9);
+    }
+
+    #[test]
+    fn mesh_falls_back_on_face_normals_for_zero_normals() {
+        let mesh = get_square_mesh(Some([2, 2, 2]));
+        let ray = Ray::new(Vector3::from((0.5, -0.5, 1.)), Vector3::from((0., 0., -1.)));
+
+        let hit = mesh.hit(&ray, 0., 10.).unwrap();
+
+        assert_eq!(Vector3::from((0., 0., 1.)), hit.normal);
+    }
+
+    #[test]
+    fn mesh_bounding_box_surrounds_all_vertices() {
+        let bounding_box = get_square_mesh(None).bounding_box().unwrap();
+
+        assert_eq!(Vector3::from((-1., -1., 0.)), bounding_box.min);
+        assert_eq!(Vector3::from((1., 1., 0.)), bounding_box.max);
+    }
+
+    #[test]
+    fn samples_triangles_uniformly_over_their_area() {
+        let mut rng = Pcg32::new(0, 0);
+        let triangle = get_triangle();
+        let origin = Vector3::default();
+
+        for _i in 0..20 {
+            let sample = triangle.sample_surface(&origin, &mut rng).unwrap();
+            let direction = sample.point - origin;
+            assert!(triangle.hit(&Ray::new(origin, direction), 0., 2.).is_some());
+            assert!((triangle.surface_pdf(&origin, &direction) - sample.pdf).abs() < 1e-9);
+        }
+    }
+}
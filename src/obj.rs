@@ -0,0 +1,581 @@
+use crate::hit::{Hittable, HittableList};
+use crate::material::{Dielectric, Lambertian, Material, Metal};
+use crate::mesh::{MeshFace, TriangleMesh};
+use crate::texture::ConstantTexture;
+use crate::vector3::Vector3;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const MAX_SHININESS: f64 = 1000.;
+/// Materials more opaque than this are kept opaque, as exporters often write a
+/// dissolve slightly under 1 for solid materials.
+const MAX_GLASS_DISSOLVE: f64 = 0.5;
+/// Index of refraction of glass, for transparent materials without `Ni`.
+const GLASS_REFRACTION_INDEX: f64 = 1.5;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io(error) => write!(f, "{}", error),
+            ObjError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl Error for ObjError {}
+
+impl From<io::Error> for ObjError {
+    fn from(error: io::Error) -> Self {
+        ObjError::Io(error)
+    }
+}
+
+fn parse_error(line: usize, message: String) -> ObjError {
+    ObjError::Parse { line, message }
+}
+
+fn parse_floats<const N: usize>(line: usize, arguments: &[&str]) -> Result<[f64; N], ObjError> {
+    if arguments.len() < N {
+        return Err(parse_error(
+            line,
+            format!("expected {} numbers, found {}", N, arguments.len()),
+        ));
+    }
+
+    let mut values = [0.; N];
+    for (value, argument) in values.iter_mut().zip(arguments) {
+        *value = argument
+            .parse()
+            .map_err(|_| parse_error(line, format!("invalid number '{}'", argument)))?;
+    }
+    Ok(values)
+}
+
+fn parse_vector(line: usize, arguments: &[&str]) -> Result<Vector3, ObjError> {
+    let [x, y, z] = parse_floats::<3>(line, arguments)?;
+    Ok(Vector3::from((x, y, z)))
+}
+
+/// Material description from a MTL file, with the MTL defaults for missing keys
+/// but `Ni`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtlMaterial {
+    pub diffuse: Vector3,
+    pub specular: Vector3,
+    pub shininess: f64,
+    pub refraction_index: Option<f64>,
+    pub dissolve: f64,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            diffuse: Vector3::from((0.8, 0.8, 0.8)),
+            specular: Vector3::default(),
+            shininess: 0.,
+            refraction_index: None,
+            dissolve: 1.,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum MaterialModel {
+    Diffuse,
+    Metal,
+    Dielectric,
+}
+
+fn max_component(v: &Vector3) -> f64 {
+    v.x.max(v.y).max(v.z)
+}
+
+impl MtlMaterial {
+    fn model(&self) -> MaterialModel {
+        if self.dissolve <= MAX_GLASS_DISSOLVE {
+            MaterialModel::Dielectric
+        } else if max_component(&self.specular) > max_component(&self.diffuse) {
+            MaterialModel::Metal
+        } else {
+            MaterialModel::Diffuse
+        }
+    }
+
+    /// Transparent materials become `Dielectric`, made of glass when `Ni` is
+    /// missing, materials more specular than
+    /// diffuse become `Metal` with a fuzziness decreasing with `Ns`, the others
+    /// are `Lambertian`.
+    pub fn to_material(&self) -> Box<dyn Material> {
+        match self.model() {
+            MaterialModel::Dielectric => Box::new(Dielectric {
+                refraction_index: self.refraction_index.unwrap_or(GLASS_REFRACTION_INDEX),
+            }),
+            MaterialModel::Metal => Box::new(Metal::new(
+                ConstantTexture::new(self.specular),
+                1. - (self.shininess / MAX_SHININESS).clamp(0., 1.),
+            )),
+            MaterialModel::Diffuse => Box::new(Lambertian::new(ConstantTexture::new(self.diffuse))),
+        }
+    }
+}
+
+pub fn parse_mtl(content: &str) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (index, line) in content.lines().enumerate() {
+        let line_number = index + 1;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let (keyword, arguments) = match tokens.split_first() {
+            Some((keyword, _)) if keyword.starts_with('#') => continue,
+            Some((keyword, arguments)) => (*keyword, arguments),
+            None => continue,
+        };
+
+        if keyword == "newmtl" {
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            let name = arguments.join(" ");
+            if name.is_empty() {
+                return Err(parse_error(line_number, "missing material name".into()));
+            }
+            current = Some((name, MtlMaterial::default()));
+            continue;
+        }
+
+        let material = match current.as_mut() {
+            Some((_, material)) => material,
+            None if ["Kd", "Ks", "Ns", "Ni", "d", "Tr"].contains(&keyword) => {
+                return Err(parse_error(
+                    line_number,
+                    format!("'{}' found before 'newmtl'", keyword),
+                ))
+            }
+            None => continue,
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = parse_vector(line_number, arguments)?,
+            "Ks" => material.specular = parse_vector(line_number, arguments)?,
+            "Ns" => material.shininess = parse_floats::<1>(line_number, arguments)?[0],
+            "Ni" => material.refraction_index = Some(parse_floats::<1>(line_number, arguments)?[0]),
+            "d" => material.dissolve = parse_floats::<1>(line_number, arguments)?[0],
+            "Tr" => material.dissolve = 1. - parse_floats::<1>(line_number, arguments)?[0],
+            _ => {}
+        }
+    }
+
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+
+    Ok(materials)
+}
+
+#[derive(Debug, Copy, Clone)]
+struct FaceVertex {
+    position: usize,
+    texcoord: Option<usize>,
+    normal: Option<usize>,
+}
+
+/// Faces of one group sharing the same material.
+struct ObjGroup {
+    material: Option<String>,
+    faces: Vec<[FaceVertex; 3]>,
+}
+
+#[derive(Default)]
+struct ObjData {
+    positions: Vec<Vector3>,
+    normals: Vec<Vector3>,
+    texcoords: Vec<(f64, f64)>,
+    groups: Vec<ObjGroup>,
+}
+
+fn resolve_index(line: usize, token: &str, count: usize) -> Result<usize, ObjError> {
+    let index: i64 = token
+        .parse()
+        .map_err(|_| parse_error(line, format!("invalid index '{}'", token)))?;
+
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+
+    if resolved < 0 || resolved >= count as i64 {
+        Err(parse_error(line, format!("index {} out of range", index)))
+    } else {
+        Ok(resolved as usize)
+    }
+}
+
+fn optional_index(
+    line: usize,
+    token: Option<&str>,
+    count: usize,
+) -> Result<Option<usize>, ObjError> {
+    match token {
+        Some(token) if !token.is_empty() => Ok(Some(resolve_index(line, token, count)?)),
+        _ => Ok(None),
+    }
+}
+
+impl ObjData {
+    fn parse_face_vertex(&self, line: usize, token: &str) -> Result<FaceVertex, ObjError> {
+        let mut parts = token.split('/');
+        let position = resolve_index(line, parts.next().unwrap(), self.positions.len())?;
+        let texcoord = optional_index(line, parts.next(), self.texcoords.len())?;
+        let normal = optional_index(line, parts.next(), self.normals.len())?;
+
+        Ok(FaceVertex {
+            position,
+            texcoord,
+            normal,
+        })
+    }
+
+    fn current_group(&mut self) -> &mut ObjGroup {
+        if self.groups.is_empty() {
+            self.start_group(None);
+        }
+        self.groups.last_mut().unwrap()
+    }
+
+    fn start_group(&mut self, material: Option<String>) {
+        self.groups.push(ObjGroup {
+            material,
+            faces: Vec::new(),
+        });
+    }
+}
+
+fn parse_obj_data<F>(
+    content: &str,
+    mut read_library: F,
+) -> Result<(ObjData, HashMap<String, MtlMaterial>), ObjError>
+where
+    F: FnMut(&str) -> Result<String, ObjError>,
+{
+    let mut data = ObjData::default();
+    let mut materials = HashMap::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line_number = index + 1;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let (keyword, arguments) = match tokens.split_first() {
+            Some((keyword, _)) if keyword.starts_with('#') => continue,
+            Some((keyword, arguments)) => (*keyword, arguments),
+            None => continue,
+        };
+
+        match keyword {
+            "v" => data.positions.push(parse_vector(line_number, arguments)?),
+            "vn" => data.normals.push(parse_vector(line_number, arguments)?),
+            "vt" => {
+                let [u, v] = parse_floats::<2>(line_number, arguments)?;
+                data.texcoords.push((u, v));
+            }
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(parse_error(
+                        line_number,
+                        "a face needs at least three vertices".into(),
+                    ));
+                }
+                let vertices = arguments
+                    .iter()
+                    .map(|token| data.parse_face_vertex(line_number, token))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let group = data.current_group();
+                for i in 1..vertices.len() - 1 {
+                    group
+                        .faces
+                        .push([vertices[0], vertices[i], vertices[i + 1]]);
+                }
+            }
+            "g" | "o" => {
+                let material = data.current_group().material.clone();
+                data.start_group(material);
+            }
+            "usemtl" => {
+                let name = arguments.join(" ");
+                if !materials.contains_key(&name) {
+                    return Err(parse_error(
+                        line_number,
+                        format!("unknown material '{}'", name),
+                    ));
+                }
+                data.start_group(Some(name));
+            }
+            "mtllib" => {
+                for library in arguments {
+                    let library_content = read_library(library)?;
+                    materials.extend(parse_mtl(&library_content)?);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok((data, materials))
+}
+
+/// Copies the attributes used by the group into buffers of its own, so each
+/// group can become an independent mesh.
+fn build_mesh(data: &ObjData, group: &ObjGroup, material: Box<dyn Material>) -> TriangleMesh {
+    fn remap<T: Copy>(
+        index: usize,
+        source: &[T],
+        target: &mut Vec<T>,
+        indices: &mut HashMap<usize, usize>,
+    ) -> usize {
+        *indices.entry(index).or_insert_with(|| {
+            target.push(source[index]);
+            target.len() - 1
+        })
+    }
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut texcoords = Vec::new();
+    let mut position_indices = HashMap::new();
+    let mut normal_indices = HashMap::new();
+    let mut texcoord_indices = HashMap::new();
+
+    let faces = group
+        .faces
+        .iter()
+        .map(|face| {
+            let mut vertices = [0; 3];
+            let mut face_normals = Some([0; 3]);
+            let mut face_texcoords = Some([0; 3]);
+
+            for (i, vertex) in face.iter().enumerate() {
+                vertices[i] = remap(
+                    vertex.position,
+                    &data.positions,
+                    &mut positions,
+                    &mut position_indices,
+                );
+                face_normals = match (face_normals, vertex.normal) {
+                    (Some(mut indices), Some(normal)) => {
+                        indices[i] =
+                            remap(normal, &data.normals, &mut normals, &mut normal_indices);
+                        Some(indices)
+                    }
+                    _ => None,
+                };
+                face_texcoords = match (face_texcoords, vertex.texcoord) {
+                    (Some(mut indices), Some(texcoord)) => {
+                        indices[i] = remap(
+                            texcoord,
+                            &data.texcoords,
+                            &mut texcoords,
+                            &mut texcoord_indices,
+                        );
+                        Some(indices)
+                    }
+                    _ => None,
+                };
+            }
+
+            MeshFace {
+                vertices,
+                normals: face_normals,
+                texcoords: face_texcoords,
+            }
+        })
+        .collect();
+
+    TriangleMesh::new(positions, normals, texcoords, faces, material)
+}
+
+/// Parses OBJ content into one mesh per group and material. `read_library` is
+/// called with the name of each `mtllib` and must return its content.
+pub fn parse_obj<F>(content: &str, read_library: F) -> Result<HittableList, ObjError>
+where
+    F: FnMut(&str) -> Result<String, ObjError>,
+{
+    let (data, materials) = parse_obj_data(content, read_library)?;
+    let default_material = MtlMaterial::default();
+
+    let meshes: Vec<Box<dyn Hittable>> = data
+        .groups
+        .iter()
+        .filter(|group| !group.faces.is_empty())
+        .map(|group| -> Box<dyn Hittable> {
+            let material = group
+                .material
+                .as_ref()
+                .map_or(&default_material, |name| &materials[name]);
+            Box::new(build_mesh(&data, group, material.to_material()))
+        })
+        .collect();
+
+    Ok(HittableList::new(meshes))
+}
+
+/// Loads an OBJ file, looking for its material libraries next to it.
+pub fn load_obj(path: &Path) -> Result<HittableList, ObjError> {
+    let content = fs::read_to_string(path)?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    parse_obj(&content, |library| {
+        Ok(fs::read_to_string(directory.join(library))?)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+
+    fn no_library(name: &str) -> Result<String, ObjError> {
+        Err(ObjError::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            name.to_string(),
+        )))
+    }
+
+    const SQUARE: &str = "
+# A unit square made of a quad
+v -1 -1 0
+v 1 -1 0
+v 1 1 0
+v -1 1 0
+vn 0 0 1
+vt 0 0
+vt 1 1
+f 1/1/1 2//1 3/2/1 4//1
+";
+
+    #[test]
+    fn parses_vertices_and_faces() {
+        let (data, _) = parse_obj_data(SQUARE, no_library).unwrap();
+
+        assert_eq!(4, data.positions.len());
+        assert_eq!(1, data.normals.len());
+        assert_eq!(2, data.texcoords.len());
+        assert_eq!(1, data.groups.len());
+    }
+
+    #[test]
+    fn triangulates_polygons() {
+        let (data, _) = parse_obj_data(SQUARE, no_library).unwrap();
+
+        let faces = &data.groups[0].faces;
+        assert_eq!(2, faces.len());
+        assert_eq!(0, faces[1][0].position);
+        assert_eq!(2, faces[1][1].position);
+        assert_eq!(3, faces[1][2].position);
+    }
+
+    #[test]
+    fn resolves_relative_indices() {
+        let (data, _) =
+            parse_obj_data("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n", no_library).unwrap();
+
+        let face = data.groups[0].faces[0];
+        assert_eq!(0, face[0].position);
+        assert_eq!(2, face[2].position);
+    }
+
+    #[test]
+    fn splits_groups_and_materials() {
+        let content = "mtllib box.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\ng first\nf 1 2 3\ng second\nusemtl red\nf 1 2 3\nf 1 3 2\n";
+        let (data, _) = parse_obj_data(content, |_| Ok("newmtl red\nKd 1 0 0\n".into())).unwrap();
+
+        let groups: Vec<_> = data.groups.iter().filter(|g| !g.faces.is_empty()).collect();
+        assert_eq!(2, groups.len());
+        assert_eq!(None, groups[0].material);
+        assert_eq!(Some("red".to_string()), groups[1].material);
+        assert_eq!(2, groups[1].faces.len());
+    }
+
+    #[test]
+    fn produces_hittable_meshes() {
+        let meshes = parse_obj(SQUARE, no_library).unwrap();
+        let ray = Ray::new(Vector3::from((0.5, 0.5, 1.)), Vector3::from((0., 0., -1.)));
+
+        let hit = meshes.hit(&ray, 0., 10.).unwrap();
+
+        assert_eq!(Vector3::from((0.5, 0.5, 0.)), hit.point);
+        assert_eq!(Vector3::from((0., 0., 1.)), hit.normal);
+    }
+
+    #[test]
+    fn reports_line_of_invalid_vertex() {
+        let error = parse_obj("v 0 0 0\nv 1 zero 0\n", no_library)
+            .err()
+            .unwrap();
+
+        assert_eq!("line 2: invalid number 'zero'", error.to_string());
+    }
+
+    #[test]
+    fn reports_out_of_range_indices() {
+        let error = parse_obj("v 0 0 0\nf 1 2 3\n", no_library).err().unwrap();
+
+        assert_eq!("line 2: index 2 out of range", error.to_string());
+    }
+
+    #[test]
+    fn reports_unknown_materials() {
+        let error = parse_obj("usemtl missing\n", no_library).err().unwrap();
+
+        assert_eq!("line 1: unknown material 'missing'", error.to_string());
+    }
+
+    #[test]
+    fn parses_mtl_materials() {
+        let content =
+            "newmtl glass\nNi 1.5\nd 0.2\n\nnewmtl gold\nKd 0.1 0.1 0\nKs 1 0.8 0\nNs 500\n";
+
+        let materials = parse_mtl(content).unwrap();
+
+        assert_eq!(2, materials.len());
+        assert_eq!(
+            MtlMaterial {
+                diffuse: Vector3::from((0.1, 0.1, 0.)),
+                specular: Vector3::from((1., 0.8, 0.)),
+                shininess: 500.,
+                refraction_index: None,
+                dissolve: 1.,
+            },
+            materials["gold"]
+        );
+        assert_eq!(Some(1.5), materials["glass"].refraction_index);
+    }
+
+    #[test]
+    fn maps_mtl_materials_on_existing_materials() {
+        let materials =
+            parse_mtl("newmtl glass\nd 0.2\nnewmtl gold\nKs 1 1 1\nnewmtl chalk\nKd 1 1 1\n")
+                .unwrap();
+
+        assert_eq!(MaterialModel::Dielectric, materials["glass"].model());
+        assert_eq!(MaterialModel::Metal, materials["gold"].model());
+        assert_eq!(MaterialModel::Diffuse, materials["chalk"].model());
+    }
+
+    #[test]
+    fn keeps_nearly_opaque_materials_opaque() {
+        let materials = parse_mtl("newmtl plastic\nKd 1 0 0\nd 0.99\n").unwrap();
+
+        assert_eq!(MaterialModel::Diffuse, materials["plastic"].model());
+    }
+}
@@ -0,0 +1,572 @@
+use clap::{Parser, ValueEnum};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use weekend_raytracer::random;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SceneChoice {
+    Scene1,
+    Scene2,
+    CornellBox,
+    File(PathBuf),
+}
+
+fn parse_scene(value: &str) -> Result<SceneChoice, String> {
+    match value {
+        "scene1" => Ok(SceneChoice::Scene1),
+        "scene2" => Ok(SceneChoice::Scene2),
+        "cornell" => Ok(SceneChoice::CornellBox),
+        path if path.ends_with(".toml") => Ok(SceneChoice::File(PathBuf::from(path))),
+        _ => Err(String::from(
+            "expected 'scene1', 'scene2', 'cornell' or a path to a .toml scene file",
+        )),
+    }
+}
+
+fn parse_positive_number(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(density) if density > 0. && density.is_finite() => Ok(density),
+        _ => Err(String::from("expected a positive number")),
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    Ppm,
+    PpmBinary,
+    Pfm,
+    Png,
+    Exr,
+    Hdr,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, ValueEnum)]
+pub enum IntegratorChoice {
+    /// Path tracing with light sampling
+    Path,
+    /// Normals of the visible surfaces
+    Normals,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, ValueEnum)]
+pub enum SamplerChoice {
+    /// Uniform random numbers
+    Independent,
+    /// Jittered grids
+    Stratified,
+    /// Randomly shifted Halton sequence
+    Halton,
+    /// Owen scrambled Sobol sequence
+    Sobol,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, ValueEnum)]
+pub enum ExrPixelType {
+    Half,
+    Float,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, ValueEnum)]
+pub enum ExrCompression {
+    None,
+    Zip,
+}
+
+/// Renders a scene with a path tracer.
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Options {
+    /// Built-in scene name ('scene1', 'scene2' or 'cornell') or path to a .toml scene file
+    #[arg(short, long, default_value = "scene2", value_parser = parse_scene)]
+    pub scene: SceneChoice,
+
+    /// Image width in pixels
+    #[arg(long, default_value_t = 800, value_parser = clap::value_parser!(u32).range(1..))]
+    pub width: u32,
+
+    /// Image height in pixels
+    #[arg(long, default_value_t = 400, value_parser = clap::value_parser!(u32).range(1..))]
+    pub height: u32,
+
+    /// Samples per pixel, or maximum number of samples per pixel with adaptive sampling
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..))]
+    pub samples: u32,
+
+    /// Relative error of the pixels under which they stop being sampled, enabling adaptive sampling
+    #[arg(long, value_parser = parse_positive_number)]
+    pub adaptive_threshold: Option<f64>,
+
+    /// Number of samples taken between checks of the error of a pixel with adaptive sampling
+    #[arg(long, default_value_t = 16, value_parser = clap::value_parser!(u32).range(1..))]
+    pub min_samples: u32,
+
+    /// Image of the number of samples taken in each pixel, relative to --samples
+    #[arg(long)]
+    pub sample_count_map: Option<PathBuf>,
+
+    /// Maximum number of bounces of a ray
+    #[arg(long, default_value_t = 50, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_depth: u32,
+
+    /// Number of bounces after which paths are randomly stopped as they carry less light
+    #[arg(long, default_value_t = 5)]
+    pub roulette_depth: u32,
+
+    /// Algorithm computing the light along camera rays
+    #[arg(long, value_enum, default_value_t = IntegratorChoice::Path)]
+    pub integrator: IntegratorChoice,
+
+    /// Distribution of the random numbers of the samples of a pixel
+    #[arg(long, value_enum, default_value_t = SamplerChoice::Sobol)]
+    pub sampler: SamplerChoice,
+
+    /// Samples per pixel of each pass over the image, the output being written after each pass
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub pass_samples: Option<u32>,
+
+    /// Minimum number of seconds between two writes of the output during a render in passes
+    #[arg(long, value_parser = parse_positive_number)]
+    pub snapshot_interval: Option<f64>,
+
+    /// File where the state of the render is saved, at the end, on interruption and periodically
+    #[arg(long)]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Number of seconds between two saves of the checkpoint
+    #[arg(long, value_parser = parse_positive_number, requires = "checkpoint")]
+    pub checkpoint_interval: Option<f64>,
+
+    /// Checkpoint of a render to continue, with the same settings and up to --samples samples
+    #[arg(long)]
+    pub resume: Option<PathBuf>,
+
+    /// Density of a white fog filling the whole scene, replacing the one of the scene file
+    #[arg(long, value_parser = parse_positive_number)]
+    pub atmosphere: Option<f64>,
+
+    /// Output file, the image is written to the standard output when absent
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Output image format, guessed from the output file extension when absent
+    #[arg(short, long, value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// Precision of the OpenEXR channels
+    #[arg(long, value_enum, default_value_t = ExrPixelType::Half)]
+    pub exr_pixel_type: ExrPixelType,
+
+    /// Compression of the OpenEXR scanlines
+    #[arg(long, value_enum, default_value_t = ExrCompression::Zip)]
+    pub exr_compression: ExrCompression,
+
+    /// Seed of the random numbers of the render and of the built-in scenes, picked at random when absent
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Number of render threads, defaults to the number of available cores
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub threads: Option<u32>,
+}
+
+/// Format matching the extension of `path`, PPM by default.
+pub fn guess_format(path: Option<&Path>) -> OutputFormat {
+    let extension = path
+        .and_then(|path| path.extension())
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+
+    match extension.as_deref() {
+        Some("png") => OutputFormat::Png,
+        Some("pfm") => OutputFormat::Pfm,
+        Some("exr") => OutputFormat::Exr,
+        Some("hdr") => OutputFormat::Hdr,
+        _ => OutputFormat::Ppm,
+    }
+}
+
+fn get_value_name<T: ValueEnum>(value: T) -> String {
+    value
+        .to_possible_value()
+        .map_or_else(String::new, |value| value.get_name().to_string())
+}
+
+impl Options {
+    pub fn output_format(&self) -> OutputFormat {
+        self.format
+            .unwrap_or_else(|| guess_format(self.output.as_deref()))
+    }
+
+    /// Number of strata of the stratified sampler. Adaptive sampling takes
+    /// samples by batches of `min_samples`, each batch being stratified on its
+    /// own, as pixels may stop after any of them.
+    pub fn get_stratum_count(&self) -> u32 {
+        match self.adaptive_threshold {
+            Some(_) => self.min_samples.min(self.samples),
+            None => self.samples,
+        }
+    }
+
+    /// Settings the samples depend on, which have to stay the same to continue
+    /// a render from a checkpoint. Scene files are told apart by the hash of
+    /// their content, but not the files they refer to.
+    pub fn get_render_settings(&self) -> io::Result<toml::Table> {
+        let scene = match &self.scene {
+            SceneChoice::Scene1 => String::from("scene1"),
+            SceneChoice::Scene2 => String::from("scene2"),
+            SceneChoice::CornellBox => String::from("cornell"),
+            SceneChoice::File(path) => path.display().to_string(),
+        };
+
+        let mut settings = toml::Table::new();
+        settings.insert(String::from("scene"), scene.into());
+        if let SceneChoice::File(path) = &self.scene {
+            let scene_hash = random::hash_bytes(&fs::read(path)?);
+            settings.insert(
+                String::from("scene_hash"),
+                format!("{:016x}", scene_hash).into(),
+            );
+        }
+        settings.insert(String::from("width"), i64::from(self.width).into());
+        settings.insert(String::from("height"), i64::from(self.height).into());
+        settings.insert(String::from("max_depth"), i64::from(self.max_depth).into());
+        settings.insert(
+            String::from("roulette_depth"),
+            i64::from(self.roulette_depth).into(),
+        );
+        settings.insert(
+            String::from("integrator"),
+            get_value_name(self.integrator).into(),
+        );
+        settings.insert(String::from("sampler"), get_value_name(self.sampler).into());
+        // More samples in other strata would not be stratified with the
+        // previous ones.
+        if self.sampler == SamplerChoice::Stratified {
+            settings.insert(
+                String::from("strata"),
+                i64::from(self.get_stratum_count()).into(),
+            );
+        }
+        if let Some(atmosphere) = self.atmosphere {
+            settings.insert(String::from("atmosphere"), atmosphere.into());
+        }
+        Ok(settings)
+    }
+}
+
+/// Name of the first setting differing between `settings` and
+/// `other_settings`.
+pub fn find_changed_setting(
+    settings: &toml::Table,
+    other_settings: &toml::Table,
+) -> Option<String> {
+    settings
+        .keys()
+        .chain(other_settings.keys())
+        .find(|key| settings.get(*key) != other_settings.get(*key))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(arguments: &[&str]) -> Result<Options, clap::Error> {
+        Options::try_parse_from(
+            std::iter::once("weekend_raytracer").chain(arguments.iter().copied()),
+        )
+    }
+
+    #[test]
+    fn has_defaults_matching_the_original_render() {
+        let options = parse(&[]).unwrap();
+
+        assert_eq!(SceneChoice::Scene2, options.scene);
+        assert_eq!((800, 400), (options.width, options.height));
+        assert_eq!(100, options.samples);
+        assert_eq!(None, options.adaptive_threshold);
+        assert_eq!(50, options.max_depth);
+        assert_eq!(5, options.roulette_depth);
+        assert_eq!(IntegratorChoice::Path, options.integrator);
+        assert_eq!(SamplerChoice::Sobol, options.sampler);
+        assert_eq!(None, options.output);
+        assert_eq!(OutputFormat::Ppm, options.output_format());
+    }
+
+    #[test]
+    fn parses_all_options() {
+        let options = parse(&[
+            "--scene",
+            "scenes/scene_1.toml",
+            "--width",
+            "320",
+            "--height",
+            "200",
+            "--samples",
+            "8",
+            "--max-depth",
+            "10",
+            "--output",
+            "render.ppm",
+            "--seed",
+            "42",
+            "--threads",
+            "2",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            SceneChoice::File(PathBuf::from("scenes/scene_1.toml")),
+            options.scene
+        );
+        assert_eq!((320, 200), (options.width, options.height));
+        assert_eq!(8, options.samples);
+        assert_eq!(10, options.max_depth);
+        assert_eq!(Some(PathBuf::from("render.ppm")), options.output);
+        assert_eq!(Some(42), options.seed);
+        assert_eq!(Some(2), options.threads);
+    }
+
+    #[test]
+    fn parses_built_in_scene_names() {
+        let options = parse(&["--scene", "cornell"]).unwrap();
+
+        assert_eq!(SceneChoice::CornellBox, options.scene);
+    }
+
+    #[test]
+    fn guesses_format_from_output_extension() {
+        let options = parse(&["--output", "render.PNG"]).unwrap();
+
+        assert_eq!(OutputFormat::Png, options.output_format());
+    }
+
+    #[test]
+    fn guesses_high_dynamic_range_formats() {
+        let exr = parse(&["--output", "render.exr"]).unwrap();
+        let hdr = parse(&["--output", "render.hdr"]).unwrap();
+        let pfm = parse(&["--output", "render.pfm"]).unwrap();
+
+        assert_eq!(OutputFormat::Exr, exr.output_format());
+        assert_eq!(OutputFormat::Hdr, hdr.output_format());
+        assert_eq!(OutputFormat::Pfm, pfm.output_format());
+    }
+
+    #[test]
+    fn parses_exr_options() {
+        let options = parse(&["--exr-pixel-type", "float", "--exr-compression", "none"]).unwrap();
+
+        assert_eq!(ExrPixelType::Float, options.exr_pixel_type);
+        assert_eq!(ExrCompression::None, options.exr_compression);
+    }
+
+    #[test]
+    fn parses_binary_ppm_format() {
+        let options = parse(&["--output", "render.ppm", "--format", "ppm-binary"]).unwrap();
+
+        assert_eq!(OutputFormat::PpmBinary, options.output_format());
+    }
+
+    #[test]
+    fn prefers_explicit_format() {
+        let options = parse(&["--output", "render.png", "--format", "ppm"]).unwrap();
+
+        assert_eq!(OutputFormat::Ppm, options.output_format());
+    }
+
+    #[test]
+    fn parses_atmosphere_density() {
+        let options = parse(&["--atmosphere", "0.05"]).unwrap();
+
+        assert_eq!(Some(0.05), options.atmosphere);
+    }
+
+    #[test]
+    fn rejects_null_atmosphere_density() {
+        assert!(parse(&["--atmosphere", "0"]).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_scene() {
+        assert!(parse(&["--scene", "scene3"]).is_err());
+    }
+
+    #[test]
+    fn rejects_empty_image() {
+        assert!(parse(&["--width", "0"]).is_err());
+    }
+
+    #[test]
+    fn parses_integrator_settings() {
+        let options = parse(&["--integrator", "normals", "--roulette-depth", "0"]).unwrap();
+
+        assert_eq!(IntegratorChoice::Normals, options.integrator);
+        assert_eq!(0, options.roulette_depth);
+        assert!(parse(&["--integrator", "whitted"]).is_err());
+    }
+
+    #[test]
+    fn parses_sampler() {
+        let options = parse(&["--sampler", "stratified"]).unwrap();
+
+        assert_eq!(SamplerChoice::Stratified, options.sampler);
+        assert!(parse(&["--sampler", "poisson"]).is_err());
+    }
+
+    #[test]
+    fn parses_adaptive_sampling_settings() {
+        let options = parse(&[
+            "--adaptive-threshold",
+            "0.02",
+            "--min-samples",
+            "8",
+            "--sample-count-map",
+            "counts.png",
+        ])
+        .unwrap();
+
+        assert_eq!(Some(0.02), options.adaptive_threshold);
+        assert_eq!(8, options.min_samples);
+        assert_eq!(Some(PathBuf::from("counts.png")), options.sample_count_map);
+        assert!(parse(&["--adaptive-threshold", "-1"]).is_err());
+    }
+
+    #[test]
+    fn parses_progressive_rendering_settings() {
+        let options = parse(&["--pass-samples", "4", "--snapshot-interval", "30"]).unwrap();
+
+        assert_eq!(Some(4), options.pass_samples);
+        assert_eq!(Some(30.), options.snapshot_interval);
+        assert!(parse(&["--pass-samples", "0"]).is_err());
+    }
+
+    #[test]
+    fn parses_checkpoint_settings() {
+        let options = parse(&[
+            "--checkpoint",
+            "render.checkpoint",
+            "--checkpoint-interval",
+            "600",
+            "--resume",
+            "render.checkpoint",
+        ])
+        .unwrap();
+
+        assert_eq!(Some(PathBuf::from("render.checkpoint")), options.checkpoint);
+        assert_eq!(Some(600.), options.checkpoint_interval);
+        assert_eq!(Some(PathBuf::from("render.checkpoint")), options.resume);
+        assert!(parse(&["--checkpoint-interval", "600"]).is_err());
+    }
+
+    #[test]
+    fn render_settings_ignore_the_sample_count() {
+        let settings = parse(&["--sampler", "halton", "--samples", "10"])
+            .unwrap()
+            .get_render_settings()
+            .unwrap();
+        let more_samples = parse(&["--sampler", "halton", "--samples", "1000"])
+            .unwrap()
+            .get_render_settings()
+            .unwrap();
+
+        assert_eq!(Some("halton"), settings["sampler"].as_str());
+        assert_eq!(None, find_changed_setting(&settings, &more_samples));
+    }
+
+    #[test]
+    fn stratified_render_settings_keep_the_sample_count() {
+        let settings = parse(&["--sampler", "stratified", "--samples", "10"])
+            .unwrap()
+            .get_render_settings()
+            .unwrap();
+        let more_samples = parse(&["--sampler", "stratified", "--samples", "1000"])
+            .unwrap()
+            .get_render_settings()
+            .unwrap();
+
+        assert_eq!(
+            Some(String::from("strata")),
+            find_changed_setting(&settings, &more_samples)
+        );
+    }
+
+    #[test]
+    fn adaptive_sampling_stratifies_each_batch() {
+        let fixed = parse(&["--samples", "64"]).unwrap();
+        let adaptive = parse(&["--samples", "64", "--adaptive-threshold", "0.1"]).unwrap();
+        let more_samples = parse(&[
+            "--samples",
+            "1000",
+            "--adaptive-threshold",
+            "0.1",
+            "--sampler",
+            "stratified",
+        ])
+        .unwrap();
+        let stratified = parse(&[
+            "--samples",
+            "64",
+            "--adaptive-threshold",
+            "0.1",
+            "--sampler",
+            "stratified",
+        ])
+        .unwrap();
+
+        assert_eq!(64, fixed.get_stratum_count());
+        assert_eq!(16, adaptive.get_stratum_count());
+        assert_eq!(
+            None,
+            find_changed_setting(
+                &stratified.get_render_settings().unwrap(),
+                &more_samples.get_render_settings().unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn render_settings_hash_scene_files() {
+        let scene_1 = parse(&["--scene", "scenes/scene_1.toml"]).unwrap();
+        let textures = parse(&["--scene", "scenes/textures.toml"]).unwrap();
+        let missing = parse(&["--scene", "scenes/missing.toml"]).unwrap();
+
+        let settings = scene_1.get_render_settings().unwrap();
+        let mut other_settings = textures.get_render_settings().unwrap();
+        other_settings.insert(String::from("scene"), settings["scene"].clone());
+
+        assert!(settings["scene_hash"].is_str());
+        assert_eq!(
+            Some(String::from("scene_hash")),
+            find_changed_setting(&settings, &other_settings)
+        );
+        assert!(missing.get_render_settings().is_err());
+    }
+
+    #[test]
+    fn finds_changed_render_settings() {
+        let settings = parse(&[]).unwrap().get_render_settings().unwrap();
+        let wider = parse(&["--width", "1024"])
+            .unwrap()
+            .get_render_settings()
+            .unwrap();
+        let foggy = parse(&["--atmosphere", "0.1"])
+            .unwrap()
+            .get_render_settings()
+            .unwrap();
+
+        assert_eq!(
+            Some(String::from("width")),
+            find_changed_setting(&settings, &wider)
+        );
+        assert_eq!(
+            Some(String::from("atmosphere")),
+            find_changed_setting(&settings, &foggy)
+        );
+    }
+
+    #[test]
+    fn rejects_zero_samples() {
+        assert!(parse(&["--samples", "0"]).is_err());
+    }
+}
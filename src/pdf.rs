@@ -0,0 +1,204 @@
+use crate::hit::Hittable;
+use crate::vector3::{random_cosine_direction, random_in_cone, Vector3};
+use rand::{Rng, RngCore};
+use std::f64::consts;
+
+/// Density of probability over solid angle of picking directions.
+pub trait Pdf {
+    fn value(&self, direction: &Vector3) -> f64;
+
+    /// Picks a direction following the density, when there is one to pick.
+    fn generate(&self, rng: &mut dyn RngCore) -> Option<Vector3>;
+}
+
+/// Uniform over all directions.
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf {
+    fn value(&self, _direction: &Vector3) -> f64 {
+        1. / (4. * consts::PI)
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Option<Vector3> {
+        Some(random_in_cone(&Vector3::from((0., 0., 1.)), -1., rng))
+    }
+}
+
+/// Proportional to the cosine with a normal over its hemisphere, like the
+/// light reflected by a diffuse surface.
+pub struct CosinePdf {
+    normal: Vector3,
+}
+
+impl CosinePdf {
+    pub fn new(normal: &Vector3) -> Self {
+        CosinePdf {
+            normal: normal.normalized(),
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: &Vector3) -> f64 {
+        let cosine = direction.normalized().dot(&self.normal);
+        cosine.max(0.) / consts::PI
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Option<Vector3> {
+        Some(random_cosine_direction(&self.normal, rng))
+    }
+}
+
+/// Directions towards the surface of an object, seen from `origin`.
+pub struct HittablePdf<'a> {
+    object: &'a dyn Hittable,
+    origin: Vector3,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(object: &'a dyn Hittable, origin: Vector3) -> Self {
+        HittablePdf { object, origin }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: &Vector3) -> f64 {
+        self.object.surface_pdf(&self.origin, direction)
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Option<Vector3> {
+        self.object
+            .sample_surface(&self.origin, rng)
+            .map(|sample| sample.point - self.origin)
+    }
+}
+
+/// Picks from `first` with the probability `first_probability`, and from
+/// `second` otherwise.
+pub struct MixturePdf<A: Pdf, B: Pdf> {
+    pub first: A,
+    pub second: B,
+    pub first_probability: f64,
+}
+
+impl<A: Pdf, B: Pdf> Pdf for MixturePdf<A, B> {
+    fn value(&self, direction: &Vector3) -> f64 {
+        self.first_probability * self.first.value(direction)
+            + (1. - self.first_probability) * self.second.value(direction)
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Option<Vector3> {
+        if rng.gen_range(0., 1.) < self.first_probability {
+            self.first.generate(rng)
+        } else {
+            self.second.generate(rng)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hit::Sphere;
+    use crate::material::Lambertian;
+    use crate::random::Pcg32;
+    use crate::texture::ConstantTexture;
+
+    const SAMPLE_COUNT: u32 = 100_000;
+
+    /// Integral over all directions of `function`, estimated with uniform
+    /// directions.
+    fn integrate<F: Fn(&Vector3) -> f64>(function: F) -> f64 {
+        let mut rng = Pcg32::new(0, 0);
+        let sum: f64 = (0..SAMPLE_COUNT)
+            .map(|_| {
+                let direction = SpherePdf.generate(&mut rng).unwrap();
+                function(&direction) / SpherePdf.value(&direction)
+            })
+            .sum();
+        sum / SAMPLE_COUNT as f64
+    }
+
+    /// Integral of `function` estimated with directions picked from `pdf`.
+    fn integrate_with<F: Fn(&Vector3) -> f64>(pdf: &dyn Pdf, function: F) -> f64 {
+        let mut rng = Pcg32::new(0, 0);
+        let sum: f64 = (0..SAMPLE_COUNT)
+            .filter_map(|_| pdf.generate(&mut rng))
+            .map(|direction| function(&direction) / pdf.value(&direction))
+            .sum();
+        sum / SAMPLE_COUNT as f64
+    }
+
+    fn get_sphere() -> Sphere {
+        Sphere::new(
+            Vector3::from((0., 3., 0.)),
+            2.5,
+            Box::new(Lambertian::new(ConstantTexture::new(Vector3::default()))),
+        )
+    }
+
+    fn assert_is_normalized(pdf: &dyn Pdf) {
+        let integral = integrate(|direction| pdf.value(direction));
+
+        assert!((integral - 1.).abs() < 0.03, "integral of {}", integral);
+    }
+
+    /// Checks that importance sampling `pdf` gives the same integral as uniform
+    /// sampling, for a function which is null where the density is.
+    fn assert_is_unbiased(pdf: &dyn Pdf) {
+        let function = |direction: &Vector3| {
+            if pdf.value(direction) > 0. {
+                1. + direction.normalized().x.powi(2)
+            } else {
+                0.
+            }
+        };
+
+        let expected = integrate(function);
+        let integral = integrate_with(pdf, function);
+
+        assert!(
+            (integral - expected).abs() < 0.03 * expected,
+            "{} instead of {}",
+            integral,
+            expected
+        );
+    }
+
+    #[test]
+    fn uniform_density_covers_the_sphere() {
+        assert!((integrate(|_| 1.) - 4. * consts::PI).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cosine_density_is_normalized_and_unbiased() {
+        let pdf = CosinePdf::new(&Vector3::from((1., 2., 0.)));
+
+        assert_is_normalized(&pdf);
+        assert_is_unbiased(&pdf);
+        assert_eq!(0., pdf.value(&Vector3::from((-1., -2., 0.))));
+    }
+
+    #[test]
+    fn density_towards_an_object_is_normalized_and_unbiased() {
+        let sphere = get_sphere();
+        let pdf = HittablePdf::new(&sphere, Vector3::default());
+
+        assert_is_normalized(&pdf);
+        assert_is_unbiased(&pdf);
+        assert_eq!(0., pdf.value(&Vector3::from((0., -1., 0.))));
+    }
+
+    #[test]
+    fn mixture_density_is_normalized_and_unbiased() {
+        let sphere = get_sphere();
+        let pdf = MixturePdf {
+            first: CosinePdf::new(&Vector3::from((0., 0., 1.))),
+            second: HittablePdf::new(&sphere, Vector3::default()),
+            first_probability: 0.3,
+        };
+
+        assert_is_normalized(&pdf);
+        assert_is_unbiased(&pdf);
+    }
+}
@@ -0,0 +1,159 @@
+use crate::vector3::Vector3;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+const POINT_COUNT: usize = 256;
+const LACUNARITY: f64 = 2.;
+const GAIN: f64 = 0.5;
+
+/// Gradient noise on a lattice of random unit vectors, interpolated
+/// trilinearly with Hermite smoothing.
+#[derive(Clone)]
+pub struct Perlin {
+    gradients: Vec<Vector3>,
+    permutations: [Vec<usize>; 3],
+}
+
+fn random_unit_vector<R: Rng>(rng: &mut R) -> Vector3 {
+    loop {
+        let vector = Vector3::from((
+            rng.gen_range(-1., 1.),
+            rng.gen_range(-1., 1.),
+            rng.gen_range(-1., 1.),
+        ));
+        let squared_norm = vector.squared_norm();
+        if squared_norm > 1e-6 && squared_norm <= 1. {
+            return vector.normalized();
+        }
+    }
+}
+
+fn random_permutation<R: Rng>(rng: &mut R) -> Vec<usize> {
+    let mut permutation: Vec<usize> = (0..POINT_COUNT).collect();
+    permutation.shuffle(rng);
+    permutation
+}
+
+fn hermite(t: f64) -> f64 {
+    t * t * (3. - 2. * t)
+}
+
+impl Perlin {
+    pub fn new<R: Rng>(rng: &mut R) -> Self {
+        Perlin {
+            gradients: (0..POINT_COUNT).map(|_| random_unit_vector(rng)).collect(),
+            permutations: [
+                random_permutation(rng),
+                random_permutation(rng),
+                random_permutation(rng),
+            ],
+        }
+    }
+
+    fn gradient(&self, corner: [i64; 3]) -> Vector3 {
+        let index = (0..3).fold(0, |index, axis| {
+            index ^ self.permutations[axis][(corner[axis] & (POINT_COUNT as i64 - 1)) as usize]
+        });
+        self.gradients[index]
+    }
+
+    /// Noise in about [-1, 1], null on the lattice points.
+    pub fn noise(&self, point: &Vector3) -> f64 {
+        let floor = [point.x.floor(), point.y.floor(), point.z.floor()];
+        let fraction = [point.x - floor[0], point.y - floor[1], point.z - floor[2]];
+        let smoothed = fraction.map(hermite);
+
+        let mut accumulation = 0.;
+        for corner in 0..8 {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let lattice_point = [0, 1, 2].map(|axis| floor[axis] as i64 + offset[axis]);
+            let weight_vector = Vector3::from((
+                fraction[0] - offset[0] as f64,
+                fraction[1] - offset[1] as f64,
+                fraction[2] - offset[2] as f64,
+            ));
+            let weight = (0..3)
+                .map(|axis| {
+                    if offset[axis] == 1 {
+                        smoothed[axis]
+                    } else {
+                        1. - smoothed[axis]
+                    }
+                })
+                .product::<f64>();
+
+            accumulation += weight * self.gradient(lattice_point).dot(&weight_vector);
+        }
+        accumulation
+    }
+
+    /// Sum of the absolute value of `depth` octaves of noise, always positive.
+    pub fn turbulence(&self, point: &Vector3, depth: u32) -> f64 {
+        self.octaves(point, depth)
+            .map(|(weight, noise)| weight * noise.abs())
+            .sum()
+    }
+
+    /// Fractional brownian motion, the signed sum of `octaves` octaves of noise.
+    pub fn fbm(&self, point: &Vector3, octaves: u32) -> f64 {
+        self.octaves(point, octaves)
+            .map(|(weight, noise)| weight * noise)
+            .sum()
+    }
+
+    fn octaves<'a>(&'a self, point: &Vector3, count: u32) -> impl Iterator<Item = (f64, f64)> + 'a {
+        let point = *point;
+        (0..count).map(move |octave| {
+            let frequency = LACUNARITY.powi(octave as i32);
+            (GAIN.powi(octave as i32), self.noise(&(point * frequency)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn get_perlin(seed: u64) -> Perlin {
+        Perlin::new(&mut StdRng::seed_from_u64(seed))
+    }
+
+    #[test]
+    fn vanishes_on_lattice_points() {
+        let perlin = get_perlin(1);
+
+        assert!(perlin.noise(&Vector3::from((3., -2., 7.))).abs() < 1e-12);
+    }
+
+    #[test]
+    fn is_deterministic_for_a_seed() {
+        let point = Vector3::from((0.3, 1.7, -4.2));
+
+        assert!((get_perlin(5).noise(&point) - get_perlin(5).noise(&point)).abs() < 1e-15);
+        assert!((get_perlin(5).noise(&point) - get_perlin(6).noise(&point)).abs() > 0.);
+    }
+
+    #[test]
+    fn is_continuous() {
+        let perlin = get_perlin(2);
+        let point = Vector3::from((0.999_999, 0.5, 0.5));
+        let next_point = Vector3::from((1.000_001, 0.5, 0.5));
+
+        assert!((perlin.noise(&point) - perlin.noise(&next_point)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn stays_in_range() {
+        let perlin = get_perlin(3);
+
+        for i in 0..1000 {
+            let t = i as f64 * 0.137;
+            let point = Vector3::from((t, t * 0.7, -t * 1.3));
+            assert!(perlin.noise(&point).abs() <= 1.);
+            assert!(perlin.turbulence(&point, 7) >= 0.);
+            assert!(perlin.fbm(&point, 6).abs() <= 2.);
+        }
+    }
+}
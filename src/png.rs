@@ -0,0 +1,145 @@
+use crate::color::Color;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+const MAX_STORED_BLOCK_SIZE: usize = 0xffff;
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    const MODULO: u32 = 65521;
+    let (a, b) = bytes.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % MODULO;
+        (a, (b + a) % MODULO)
+    });
+    (b << 16) | a
+}
+
+/// Wraps the data in a zlib stream made of uncompressed deflate blocks.
+fn zlib_store(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+
+    let mut blocks = data.chunks(MAX_STORED_BLOCK_SIZE).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let length = block.len() as u16;
+        stream.push(is_final as u8);
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn push_chunk(content: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    content.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let mut typed_data = chunk_type.to_vec();
+    typed_data.extend_from_slice(data);
+    content.extend_from_slice(&typed_data);
+    content.extend_from_slice(&crc32(&typed_data).to_be_bytes());
+}
+
+/// Builds an 8 bits RGB PNG image, with the same pixel order as the PPM writer.
+pub fn get_file_content<F>(width: u32, height: u32, pixels: F) -> Vec<u8>
+where
+    F: Fn(u32, u32) -> Color,
+{
+    let mut header = Vec::new();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut scanlines = Vec::with_capacity(((width * 3 + 1) * height) as usize);
+    for y in (0u32..height).rev() {
+        scanlines.push(0);
+        for x in 0u32..width {
+            let (r, g, b): (u8, u8, u8) = pixels(x, y).into();
+            scanlines.extend_from_slice(&[r, g, b]);
+        }
+    }
+
+    let mut content = SIGNATURE.to_vec();
+    push_chunk(&mut content, b"IHDR", &header);
+    push_chunk(&mut content, b"IDAT", &zlib_store(&scanlines));
+    push_chunk(&mut content, b"IEND", &[]);
+
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_crc32() {
+        assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+    }
+
+    #[test]
+    fn computes_adler32() {
+        assert_eq!(0x11e6_0398, adler32(b"Wikipedia"));
+    }
+
+    #[test]
+    fn stores_data_in_final_block() {
+        let stream = zlib_store(b"abc");
+
+        assert_eq!(
+            vec![0x78, 0x01, 1, 3, 0, 0xfc, 0xff, b'a', b'b', b'c', 0x02, 0x4d, 0x01, 0x27],
+            stream
+        );
+    }
+
+    #[test]
+    fn splits_large_data_in_several_blocks() {
+        let data = vec![0u8; MAX_STORED_BLOCK_SIZE + 10];
+
+        let stream = zlib_store(&data);
+
+        assert_eq!(0, stream[2]);
+        assert_eq!(1, stream[2 + 5 + MAX_STORED_BLOCK_SIZE]);
+        assert_eq!(2 + 5 * 2 + data.len() + 4, stream.len());
+    }
+
+    #[test]
+    fn writes_signature_and_chunks() {
+        let content = get_file_content(2, 1, |_, _| Color::new(255, 0, 0));
+
+        assert_eq!(&SIGNATURE, &content[0..8]);
+        assert_eq!(b"IHDR", &content[12..16]);
+        assert_eq!(&[0, 0, 0, 2, 0, 0, 0, 1, 8, 2, 0, 0, 0], &content[16..29]);
+        assert_eq!(b"IEND", &content[content.len() - 8..content.len() - 4]);
+        assert_eq!(&[0xae, 0x42, 0x60, 0x82], &content[content.len() - 4..]);
+    }
+
+    #[test]
+    fn writes_rows_from_top_to_bottom() {
+        let content = get_file_content(1, 2, |_, y| Color::new(y as u8, 0, 0));
+
+        let idat_data = &content[8 + 25 + 8..];
+        let scanlines = &idat_data[7..15];
+        assert_eq!(&[0, 1, 0, 0, 0, 0, 0, 0], scanlines);
+    }
+}
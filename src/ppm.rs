@@ -1,4 +1,35 @@
 use crate::color::Color;
+use crate::radiance::Radiance;
+use crate::render::Framebuffer;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub enum PpmError {
+    Io(io::Error),
+    Parse(String),
+}
+
+impl fmt::Display for PpmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PpmError::Io(error) => write!(f, "{}", error),
+            PpmError::Parse(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for PpmError {}
+
+impl From<io::Error> for PpmError {
+    fn from(error: io::Error) -> Self {
+        PpmError::Io(error)
+    }
+}
 
 pub fn get_file_content<F>(width: u32, height: u32, pixels: F) -> String
 where
@@ -11,9 +42,337 @@ where
         for x in 0u32..width {
             let color = pixels(x, y);
             content.push_str(&color.to_string());
-            content.push_str("\n");
+            content.push('\n');
+        }
+    }
+
+    content
+}
+
+/// Builds a binary P6 image, with the same pixel order as the ASCII writer.
+pub fn get_binary_file_content<F>(width: u32, height: u32, pixels: F) -> Vec<u8>
+where
+    F: Fn(u32, u32) -> Color,
+{
+    let mut content = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    content.reserve((width * height * 3) as usize);
+
+    for y in (0u32..height).rev() {
+        for x in 0u32..width {
+            let (r, g, b): (u8, u8, u8) = pixels(x, y).into();
+            content.extend_from_slice(&[r, g, b]);
+        }
+    }
+
+    content
+}
+
+/// Builds a little endian Portable Float Map holding linear values. PFM rows are
+/// stored from the bottom of the image.
+pub fn get_pfm_file_content<F>(width: u32, height: u32, pixels: F) -> Vec<u8>
+where
+    F: Fn(u32, u32) -> Radiance,
+{
+    let mut content = format!("PF\n{} {}\n-1.0\n", width, height).into_bytes();
+    content.reserve((width * height * 3 * 4) as usize);
+
+    for y in 0u32..height {
+        for x in 0u32..width {
+            let radiance = pixels(x, y);
+            for value in &[radiance.r, radiance.g, radiance.b] {
+                content.extend_from_slice(&(*value as f32).to_le_bytes());
+            }
         }
     }
 
     content
 }
+
+struct HeaderReader<'a> {
+    content: &'a [u8],
+    position: usize,
+}
+
+impl<'a> HeaderReader<'a> {
+    fn new(content: &'a [u8]) -> Self {
+        HeaderReader {
+            content,
+            position: 0,
+        }
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&byte) = self.content.get(self.position) {
+            if byte == b'#' {
+                while self
+                    .content
+                    .get(self.position)
+                    .is_some_and(|&byte| byte != b'\n')
+                {
+                    self.position += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn token(&mut self) -> Option<&'a str> {
+        self.skip_whitespace_and_comments();
+        let start = self.position;
+        while self
+            .content
+            .get(self.position)
+            .is_some_and(|byte| !byte.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.content[start..self.position])
+            .ok()
+            .filter(|token| !token.is_empty())
+    }
+
+    fn value<T: FromStr>(&mut self, name: &str) -> Result<T, PpmError> {
+        self.token()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| PpmError::Parse(format!("invalid {}", name)))
+    }
+
+    fn dimensions(&mut self) -> Result<(u32, u32), PpmError> {
+        let width = self.value("width")?;
+        let height = self.value("height")?;
+        if width == 0 || height == 0 {
+            return Err(PpmError::Parse(String::from("empty image")));
+        }
+        Ok((width, height))
+    }
+
+    /// Skips the single whitespace separating the header from binary data.
+    fn binary_data(&self, length: usize) -> Result<&'a [u8], PpmError> {
+        self.content
+            .get(self.position + 1..)
+            .and_then(|data| data.get(..length))
+            .ok_or_else(|| PpmError::Parse(String::from("truncated pixel data")))
+    }
+}
+
+fn to_8_bits(value: u32, max_value: u32) -> u8 {
+    if max_value == 255 {
+        value as u8
+    } else {
+        ((value * 255 + max_value / 2) / max_value) as u8
+    }
+}
+
+/// Reads an ASCII (P3) or binary (P6) image, with samples up to 16 bits scaled
+/// to 8 bits.
+pub fn parse_ppm(content: &[u8]) -> Result<Framebuffer<Color>, PpmError> {
+    let mut header = HeaderReader::new(content);
+    let is_binary = match header.token() {
+        Some("P3") => false,
+        Some("P6") => true,
+        _ => return Err(PpmError::Parse(String::from("expected P3 or P6"))),
+    };
+    let (width, height) = header.dimensions()?;
+    let max_value: u32 = header.value("maximum value")?;
+    if max_value == 0 || max_value > 0xffff {
+        return Err(PpmError::Parse(String::from("invalid maximum value")));
+    }
+
+    let sample_count = width as usize * height as usize * 3;
+    let samples: Vec<u32> = if is_binary {
+        let sample_size = if max_value < 256 { 1 } else { 2 };
+        header
+            .binary_data(sample_count * sample_size)?
+            .chunks(sample_size)
+            .map(|bytes| {
+                bytes
+                    .iter()
+                    .fold(0, |value, &byte| value << 8 | byte as u32)
+            })
+            .collect()
+    } else {
+        (0..sample_count)
+            .map(|_| header.value("sample"))
+            .collect::<Result<_, _>>()?
+    };
+
+    if samples.iter().any(|&sample| sample > max_value) {
+        return Err(PpmError::Parse(String::from(
+            "sample above the maximum value",
+        )));
+    }
+
+    let mut image = Framebuffer::new(width, height);
+    for (index, rgb) in samples.chunks(3).enumerate() {
+        let x = index as u32 % width;
+        let y = height - 1 - index as u32 / width;
+        let [r, g, b] = [rgb[0], rgb[1], rgb[2]].map(|sample| to_8_bits(sample, max_value));
+        image.set(x, y, Color::new(r, g, b));
+    }
+
+    Ok(image)
+}
+
+/// Reads a color (PF) or greyscale (Pf) Portable Float Map, in either byte
+/// order.
+pub fn parse_pfm(content: &[u8]) -> Result<Framebuffer<Radiance>, PpmError> {
+    let mut header = HeaderReader::new(content);
+    let channel_count = match header.token() {
+        Some("PF") => 3,
+        Some("Pf") => 1,
+        _ => return Err(PpmError::Parse(String::from("expected PF or Pf"))),
+    };
+    let (width, height) = header.dimensions()?;
+    let scale: f32 = header.value("scale")?;
+    if scale == 0. || !scale.is_finite() {
+        return Err(PpmError::Parse(String::from("invalid scale")));
+    }
+
+    let value_count = width as usize * height as usize * channel_count;
+    let data = header.binary_data(value_count * 4)?;
+    let values: Vec<f64> = data
+        .chunks(4)
+        .map(|bytes| {
+            let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+            let value = if scale < 0. {
+                f32::from_le_bytes(bytes)
+            } else {
+                f32::from_be_bytes(bytes)
+            };
+            value as f64
+        })
+        .collect();
+
+    let mut image = Framebuffer::new(width, height);
+    for (index, pixel) in values.chunks(channel_count).enumerate() {
+        let x = index as u32 % width;
+        let y = index as u32 / width;
+        let radiance = match *pixel {
+            [r, g, b] => Radiance::new(r, g, b),
+            [grey] => Radiance::new(grey, grey, grey),
+            _ => unreachable!(),
+        };
+        image.set(x, y, radiance);
+    }
+
+    Ok(image)
+}
+
+pub fn load_ppm(path: &Path) -> Result<Framebuffer<Color>, PpmError> {
+    parse_ppm(&fs::read(path)?)
+}
+
+pub fn load_pfm(path: &Path) -> Result<Framebuffer<Radiance>, PpmError> {
+    parse_pfm(&fs::read(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(x: u32, y: u32) -> Color {
+        Color::new(x as u8 * 10, y as u8 * 20, 255)
+    }
+
+    #[test]
+    fn writes_binary_header_and_samples_from_the_top() {
+        let content = get_binary_file_content(1, 2, |_, y| Color::new(y as u8, 2, 3));
+
+        assert_eq!(b"P6\n1 2\n255\n\x01\x02\x03\x00\x02\x03", &content[..]);
+    }
+
+    #[test]
+    fn writes_float_map_from_the_bottom() {
+        let content = get_pfm_file_content(1, 2, |_, y| Radiance::new(y as f64, 0.5, 2.));
+
+        let header = b"PF\n1 2\n-1.0\n";
+        assert_eq!(&header[..], &content[..header.len()]);
+        assert_eq!(
+            &0f32.to_le_bytes(),
+            &content[header.len()..header.len() + 4]
+        );
+        assert_eq!(header.len() + 2 * 3 * 4, content.len());
+    }
+
+    #[test]
+    fn reads_back_ascii_images() {
+        let content = get_file_content(3, 2, gradient);
+
+        let image = parse_ppm(content.as_bytes()).unwrap();
+
+        assert_eq!((3, 2), (image.width, image.height));
+        assert_eq!(gradient(2, 1), image.get(2, 1));
+        assert_eq!(gradient(1, 0), image.get(1, 0));
+    }
+
+    #[test]
+    fn reads_back_binary_images() {
+        let content = get_binary_file_content(3, 2, gradient);
+
+        let image = parse_ppm(&content).unwrap();
+
+        assert_eq!(gradient(2, 1), image.get(2, 1));
+        assert_eq!(gradient(0, 0), image.get(0, 0));
+    }
+
+    #[test]
+    fn reads_back_float_maps() {
+        let pixels = |x, y| Radiance::new(x as f64, y as f64, 12.5);
+        let content = get_pfm_file_content(2, 3, pixels);
+
+        let image = parse_pfm(&content).unwrap();
+
+        assert_eq!(pixels(1, 2), image.get(1, 2));
+        assert_eq!(pixels(0, 1), image.get(0, 1));
+    }
+
+    #[test]
+    fn reads_big_endian_greyscale_float_maps() {
+        let mut content = b"Pf 1 1 1.0\n".to_vec();
+        content.extend_from_slice(&4f32.to_be_bytes());
+
+        let image = parse_pfm(&content).unwrap();
+
+        assert_eq!(Radiance::new(4., 4., 4.), image.get(0, 0));
+    }
+
+    #[test]
+    fn skips_comments_and_scales_samples() {
+        let content = b"P3\n# a comment\n1 1 # another one\n15\n15 0 5\n";
+
+        let image = parse_ppm(content).unwrap();
+
+        assert_eq!(Color::new(255, 0, 85), image.get(0, 0));
+    }
+
+    #[test]
+    fn reads_sixteen_bits_binary_samples() {
+        let content = b"P6 1 1 65535\n\xff\xff\x00\x00\x80\x00";
+
+        let image = parse_ppm(content).unwrap();
+
+        assert_eq!(Color::new(255, 0, 128), image.get(0, 0));
+    }
+
+    #[test]
+    fn reports_truncated_data() {
+        let error = parse_ppm(b"P6 2 1 255\n\x00\x00\x00").err().unwrap();
+
+        assert_eq!("truncated pixel data", error.to_string());
+    }
+
+    #[test]
+    fn reports_unknown_format() {
+        let error = parse_ppm(b"P1 1 1\n1").err().unwrap();
+
+        assert_eq!("expected P3 or P6", error.to_string());
+    }
+
+    #[test]
+    fn reports_samples_above_maximum() {
+        assert!(parse_ppm(b"P3 1 1 10 11 0 0").is_err());
+    }
+}
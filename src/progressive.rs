@@ -0,0 +1,162 @@
+use crate::adaptive::{AdaptiveSampling, PixelStatistics};
+use crate::radiance::Radiance;
+use crate::render::{self, Framebuffer};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Takes up to `pass_samples` more samples in every pixel of `statistics`
+/// still needing some. `pixel_sampler` gives for the pixel at `(x, y)` the
+/// radiance of its sample with the given index: samples continue where the
+/// previous pass stopped, so rendering in passes gives the same image as
+/// rendering at once.
+///
+/// Setting `interrupted` ends the pass early, leaving the remaining pixels as
+/// they were.
+pub fn render_pass<F, S>(
+    statistics: &Framebuffer<PixelStatistics>,
+    thread_count: usize,
+    pass_samples: u32,
+    sampling: &AdaptiveSampling,
+    pixel_sampler: F,
+    interrupted: &AtomicBool,
+) -> Framebuffer<PixelStatistics>
+where
+    F: Fn(u32, u32) -> S + Sync,
+    S: FnMut(u32) -> Radiance,
+{
+    render::render(statistics.width, statistics.height, thread_count, |x, y| {
+        let mut pixel = statistics.get(x, y);
+        if sampling.is_done(&pixel) {
+            return pixel;
+        }
+        let mut sample = pixel_sampler(x, y);
+        let pass_end = pixel.count.saturating_add(pass_samples);
+        while pixel.count < pass_end
+            && !sampling.is_done(&pixel)
+            && !interrupted.load(Ordering::Relaxed)
+        {
+            pixel.add(sample(pixel.count));
+        }
+        pixel
+    })
+}
+
+/// Whether every pixel of `statistics` has all the samples it needs.
+pub fn is_complete(statistics: &Framebuffer<PixelStatistics>, sampling: &AdaptiveSampling) -> bool {
+    (0..statistics.height)
+        .all(|y| (0..statistics.width).all(|x| sampling.is_done(&statistics.get(x, y))))
+}
+
+/// Mean radiance of every pixel.
+pub fn get_image(statistics: &Framebuffer<PixelStatistics>) -> Framebuffer<Radiance> {
+    let mut image = Framebuffer::new(statistics.width, statistics.height);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            image.set(x, y, statistics.get(x, y).mean());
+        }
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::hash_to_unit;
+
+    fn pixel_sampler(x: u32, y: u32) -> impl FnMut(u32) -> Radiance {
+        move |index| {
+            let value = hash_to_unit(&[f64::from(x), f64::from(y), f64::from(index)]);
+            Radiance::new(value, 1., 0.)
+        }
+    }
+
+    #[test]
+    fn passes_give_the_same_image_as_a_single_render() {
+        let sampling = AdaptiveSampling::fixed(10);
+        let empty = Framebuffer::new(5, 3);
+
+        let single = render_pass(
+            &empty,
+            2,
+            u32::MAX,
+            &sampling,
+            pixel_sampler,
+            &AtomicBool::new(false),
+        );
+        let mut progressive = empty;
+        for _ in 0..4 {
+            progressive = render_pass(
+                &progressive,
+                2,
+                3,
+                &sampling,
+                pixel_sampler,
+                &AtomicBool::new(false),
+            );
+        }
+
+        for y in 0..3 {
+            for x in 0..5 {
+                assert_eq!(single.get(x, y), progressive.get(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn render_is_complete_once_all_samples_are_taken() {
+        let sampling = AdaptiveSampling::fixed(4);
+        let empty = Framebuffer::new(2, 2);
+
+        let first_pass = render_pass(
+            &empty,
+            1,
+            3,
+            &sampling,
+            pixel_sampler,
+            &AtomicBool::new(false),
+        );
+        let second_pass = render_pass(
+            &first_pass,
+            1,
+            3,
+            &sampling,
+            pixel_sampler,
+            &AtomicBool::new(false),
+        );
+
+        assert_eq!(3, first_pass.get(1, 1).count);
+        assert!(!is_complete(&first_pass, &sampling));
+        assert_eq!(4, second_pass.get(1, 1).count);
+        assert!(is_complete(&second_pass, &sampling));
+    }
+
+    #[test]
+    fn interrupted_passes_leave_pixels_unchanged() {
+        let sampling = AdaptiveSampling::fixed(4);
+        let empty = Framebuffer::new(2, 2);
+
+        let pass = render_pass(
+            &empty,
+            1,
+            4,
+            &sampling,
+            pixel_sampler,
+            &AtomicBool::new(true),
+        );
+
+        assert_eq!(empty, pass);
+    }
+
+    #[test]
+    fn image_holds_the_mean_of_the_samples() {
+        let mut statistics = Framebuffer::new(1, 1);
+        let mut pixel = PixelStatistics::default();
+        pixel.add(Radiance::new(1., 0., 0.));
+        pixel.add(Radiance::new(0., 0., 1.));
+        statistics.set(0, 0, pixel);
+
+        assert_eq!(
+            Radiance::new(0.5, 0., 0.5),
+            get_image(&statistics).get(0, 0)
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use crate::color::Color;
+use crate::vector3::Vector3;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul};
+
+/// Linear light intensity, unbounded, carried through the integration. It is
+/// only quantized to a `Color` when writing low dynamic range images.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Radiance {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+}
+
+impl Radiance {
+    pub fn new(r: f64, g: f64, b: f64) -> Self {
+        Radiance { r, g, b }
+    }
+
+    pub fn lerp(self, other: Radiance, t: f64) -> Radiance {
+        assert!(t <= 1.);
+        assert!(t >= 0.);
+
+        self * (1. - t) + other * t
+    }
+
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    pub fn gamma_corrected(&self) -> Radiance {
+        Radiance {
+            r: self.r.max(0.).sqrt(),
+            g: self.g.max(0.).sqrt(),
+            b: self.b.max(0.).sqrt(),
+        }
+    }
+}
+
+impl Add for Radiance {
+    type Output = Radiance;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Radiance {
+            r: self.r + rhs.r,
+            g: self.g + rhs.g,
+            b: self.b + rhs.b,
+        }
+    }
+}
+
+impl AddAssign for Radiance {
+    fn add_assign(&mut self, rhs: Self) {
+        self.r += rhs.r;
+        self.g += rhs.g;
+        self.b += rhs.b;
+    }
+}
+
+impl Mul<f64> for Radiance {
+    type Output = Radiance;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        Radiance {
+            r: self.r * rhs,
+            g: self.g * rhs,
+            b: self.b * rhs,
+        }
+    }
+}
+
+/// Attenuation by a surface, with one factor per channel.
+impl Mul<Vector3> for Radiance {
+    type Output = Radiance;
+
+    fn mul(self, rhs: Vector3) -> Self::Output {
+        Radiance {
+            r: self.r * rhs.x,
+            g: self.g * rhs.y,
+            b: self.b * rhs.z,
+        }
+    }
+}
+
+impl Div<f64> for Radiance {
+    type Output = Radiance;
+
+    fn div(self, rhs: f64) -> Self::Output {
+        Radiance {
+            r: self.r / rhs,
+            g: self.g / rhs,
+            b: self.b / rhs,
+        }
+    }
+}
+
+impl DivAssign<f64> for Radiance {
+    fn div_assign(&mut self, rhs: f64) {
+        self.r /= rhs;
+        self.g /= rhs;
+        self.b /= rhs;
+    }
+}
+
+/// Clamps each channel to [0, 1] before quantization.
+impl From<Radiance> for Color {
+    fn from(radiance: Radiance) -> Self {
+        Color::from((
+            radiance.r.clamp(0., 1.),
+            radiance.g.clamp(0., 1.),
+            radiance.b.clamp(0., 1.),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_be_added() {
+        let r1 = Radiance::new(1., 2., 3.);
+        let r2 = Radiance::new(0.5, 0.5, 0.5);
+
+        assert_eq!(Radiance::new(1.5, 2.5, 3.5), r1 + r2);
+    }
+
+    #[test]
+    fn can_be_attenuated() {
+        let radiance = Radiance::new(2., 2., 2.);
+        let attenuation = Vector3::from((0.5, 0.25, 1.));
+
+        assert_eq!(Radiance::new(1., 0.5, 2.), radiance * attenuation);
+    }
+
+    #[test]
+    fn can_be_divided_by_a_scalar() {
+        let mut radiance = Radiance::new(2., 4., 6.);
+
+        radiance /= 2.;
+
+        assert_eq!(Radiance::new(1., 2., 3.), radiance);
+    }
+
+    #[test]
+    fn can_lerp_with_another_radiance() {
+        let r1 = Radiance::new(0., 0., 0.);
+        let r2 = Radiance::new(2., 4., 8.);
+
+        assert_eq!(Radiance::new(1., 2., 4.), r1.lerp(r2, 0.5));
+    }
+
+    #[test]
+    fn keeps_values_above_one() {
+        let radiance = Radiance::new(10., 0.5, 0.) * 2.;
+
+        assert_eq!(Radiance::new(20., 1., 0.), radiance);
+    }
+
+    #[test]
+    fn is_clamped_when_quantized() {
+        let color = Color::from(Radiance::new(10., 1., -1.));
+
+        assert_eq!(Color::new(255, 255, 0), color);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn gives_luminance() {
+        assert_eq!(1., Radiance::new(1., 1., 1.).luminance());
+    }
+}
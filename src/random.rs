@@ -0,0 +1,210 @@
+use rand::{Error, RngCore};
+
+const MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Small and fast generator (O'Neill's PCG32), whose independent streams give
+/// each pixel its own sequence of random numbers. Renders then only depend on
+/// the seed, whatever the order in which pixels are computed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+impl Pcg32 {
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Pcg32 {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+
+    /// Generator of the pixel at `(x, y)` for a render seeded with `seed`.
+    pub fn for_pixel(seed: u64, x: u32, y: u32) -> Self {
+        Pcg32::new(seed, (u64::from(y) << 32) | u64::from(x))
+    }
+
+    /// Skips the next `delta` numbers of the sequence, in logarithmic time
+    /// (Brown, "Random Number Generation with Arbitrary Strides", 1994).
+    pub fn advance(&mut self, mut delta: u64) {
+        let (mut multiplier, mut increment) = (MULTIPLIER, self.increment);
+        let (mut total_multiplier, mut total_increment) = (1u64, 0u64);
+        while delta > 0 {
+            if delta & 1 == 1 {
+                total_multiplier = total_multiplier.wrapping_mul(multiplier);
+                total_increment = total_increment
+                    .wrapping_mul(multiplier)
+                    .wrapping_add(increment);
+            }
+            increment = multiplier.wrapping_add(1).wrapping_mul(increment);
+            multiplier = multiplier.wrapping_mul(multiplier);
+            delta >>= 1;
+        }
+        self.state = total_multiplier
+            .wrapping_mul(self.state)
+            .wrapping_add(total_increment);
+    }
+
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(MULTIPLIER)
+            .wrapping_add(self.increment);
+    }
+}
+
+impl RngCore for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+        self.step();
+        let xor_shifted = (((state >> 18) ^ state) >> 27) as u32;
+        xor_shifted.rotate_right((state >> 59) as u32)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let low = u64::from(self.next_u32());
+        (u64::from(self.next_u32()) << 32) | low
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+fn mix(value: u64) -> u64 {
+    let value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    let value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+/// Well spread 64 bits hash of `values`, which turns coordinates like those of
+/// a pixel into seeds.
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0, |hash, value| combine(hash, *value))
+}
+
+fn combine(hash: u64, value: u64) -> u64 {
+    mix(hash.wrapping_add(value).wrapping_add(GOLDEN_GAMMA))
+}
+
+/// Hash of the whole content of `bytes`, to tell files apart.
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    let words: Vec<u64> = bytes
+        .chunks(8)
+        .map(|chunk| {
+            let mut word = [0u8; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u64::from_le_bytes(word)
+        })
+        .collect();
+    combine(hash(&words), bytes.len() as u64)
+}
+
+/// Number in `(0, 1]` looking random but only depending on `values`, for the
+/// places which cannot be given a generator.
+pub fn hash_to_unit(values: &[f64]) -> f64 {
+    let hash = values
+        .iter()
+        .fold(0, |hash, value| combine(hash, value.to_bits()));
+    ((hash >> 11) + 1) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn same_seed_and_stream_give_the_same_sequence() {
+        let mut first = Pcg32::for_pixel(42, 3, 7);
+        let mut second = Pcg32::for_pixel(42, 3, 7);
+
+        for _ in 0..100 {
+            assert_eq!(first.next_u32(), second.next_u32());
+        }
+    }
+
+    #[test]
+    fn pixels_and_seeds_have_their_own_sequences() {
+        let sequence = |mut rng: Pcg32| -> Vec<u32> { (0..4).map(|_| rng.next_u32()).collect() };
+
+        let reference = sequence(Pcg32::for_pixel(42, 3, 7));
+
+        assert_ne!(reference, sequence(Pcg32::for_pixel(42, 7, 3)));
+        assert_ne!(reference, sequence(Pcg32::for_pixel(43, 3, 7)));
+    }
+
+    #[test]
+    fn matches_the_reference_implementation() {
+        // First outputs of pcg32-demo, seeded with 42 on stream 54.
+        let mut rng = Pcg32::new(42, 54);
+
+        assert_eq!(0xa15c_02b7, rng.next_u32());
+        assert_eq!(0x7b47_f409, rng.next_u32());
+        assert_eq!(0xba1d_3330, rng.next_u32());
+    }
+
+    #[test]
+    fn advancing_skips_numbers() {
+        let mut stepped = Pcg32::for_pixel(42, 3, 7);
+        let mut advanced = stepped.clone();
+
+        for _ in 0..1000 {
+            stepped.next_u32();
+        }
+        advanced.advance(1000);
+
+        assert_eq!(stepped, advanced);
+    }
+
+    #[test]
+    fn fills_bytes_of_any_length() {
+        let mut bytes = [0u8; 7];
+
+        Pcg32::new(1, 1).fill_bytes(&mut bytes);
+
+        assert_ne!([0u8; 7], bytes);
+    }
+
+    #[test]
+    fn draws_floats_in_unit_range() {
+        let mut rng = Pcg32::new(5, 0);
+
+        for _ in 0..1000 {
+            let value: f64 = rng.gen_range(0., 1.);
+            assert!((0. ..1.).contains(&value));
+        }
+    }
+
+    #[test]
+    fn hashes_every_byte() {
+        let first = hash_bytes(b"look_from = [0, 0, 0]");
+
+        assert_eq!(first, hash_bytes(b"look_from = [0, 0, 0]"));
+        assert_ne!(first, hash_bytes(b"look_from = [0, 0, 1]"));
+        assert_ne!(hash_bytes(b"a"), hash_bytes(b"a\0"));
+    }
+
+    #[test]
+    fn hashes_values_to_unit_range() {
+        let first = hash_to_unit(&[1., 2., 3.]);
+
+        assert_eq!(first, hash_to_unit(&[1., 2., 3.]));
+        assert_ne!(first, hash_to_unit(&[1., 2., 3.000_000_1]));
+        assert_ne!(first, hash_to_unit(&[3., 2., 1.]));
+        assert!(first > 0. && first <= 1.);
+    }
+}
@@ -1,13 +1,23 @@
 use crate::vector3::Vector3;
 
+#[derive(Debug, Copy, Clone)]
 pub struct Ray {
     pub origin: Vector3,
     pub direction: Vector3,
+    pub time: f64,
 }
 
 impl Ray {
     pub fn new(origin: Vector3, direction: Vector3) -> Self {
-        Ray { origin, direction }
+        Ray::with_time(origin, direction, 0.)
+    }
+
+    pub fn with_time(origin: Vector3, direction: Vector3, time: f64) -> Self {
+        Ray {
+            origin,
+            direction,
+            time,
+        }
     }
 
     pub fn point_at_parameter(&self, t: f64) -> Vector3 {
@@ -0,0 +1,153 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+const TILE_SIZE: u32 = 32;
+
+#[derive(Debug, PartialEq)]
+pub struct Framebuffer<T> {
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<T>,
+}
+
+impl<T: Copy + Default> Framebuffer<T> {
+    pub fn new(width: u32, height: u32) -> Self {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![T::default(); (width * height) as usize],
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> T {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, value: T) {
+        let index = self.index(x, y);
+        self.pixels[index] = value;
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height);
+        (y * self.width + x) as usize
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+fn get_tiles(width: u32, height: u32) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(TILE_SIZE as usize) {
+        for x in (0..width).step_by(TILE_SIZE as usize) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: TILE_SIZE.min(width - x),
+                height: TILE_SIZE.min(height - y),
+            });
+        }
+    }
+    tiles
+}
+
+pub fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, |count| count.get())
+}
+
+/// Evaluates `pixels` for every pixel of the image, splitting the image in tiles
+/// that are picked up by `thread_count` worker threads.
+pub fn render<T, F>(width: u32, height: u32, thread_count: usize, pixels: F) -> Framebuffer<T>
+where
+    T: Copy + Default + Send,
+    F: Fn(u32, u32) -> T + Sync,
+{
+    let tiles = get_tiles(width, height);
+    let next_tile = AtomicUsize::new(0);
+
+    let rendered_tiles: Vec<(Tile, Vec<T>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..thread_count.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut rendered = Vec::new();
+                    while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                        let mut tile_pixels =
+                            Vec::with_capacity((tile.width * tile.height) as usize);
+                        for y in tile.y..tile.y + tile.height {
+                            for x in tile.x..tile.x + tile.width {
+                                tile_pixels.push(pixels(x, y));
+                            }
+                        }
+                        rendered.push((*tile, tile_pixels));
+                    }
+                    rendered
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("A render thread panicked"))
+            .collect()
+    });
+
+    let mut framebuffer = Framebuffer::new(width, height);
+    for (tile, tile_pixels) in rendered_tiles {
+        let mut tile_pixels = tile_pixels.into_iter();
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                framebuffer.set(x, y, tile_pixels.next().unwrap());
+            }
+        }
+    }
+
+    framebuffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_the_whole_image() {
+        let tiles = get_tiles(70, 40);
+
+        let covered_pixels: u32 = tiles.iter().map(|tile| tile.width * tile.height).sum();
+
+        assert_eq!(6, tiles.len());
+        assert_eq!(70 * 40, covered_pixels);
+        assert_eq!(
+            Tile {
+                x: 64,
+                y: 32,
+                width: 6,
+                height: 8
+            },
+            tiles[5]
+        );
+    }
+
+    #[test]
+    fn renders_every_pixel_at_its_place() {
+        let framebuffer = render(70, 40, 4, |x, y| (x, y));
+
+        for y in 0..40 {
+            for x in 0..70 {
+                assert_eq!((x, y), framebuffer.get(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn renders_with_a_single_thread() {
+        let framebuffer = render(3, 2, 1, |x, y| x + y * 3);
+
+        assert_eq!(5, framebuffer.get(2, 1));
+    }
+}
@@ -0,0 +1,538 @@
+use crate::random::{hash, Pcg32};
+use rand::{Error, Rng, RngCore};
+
+/// Random numbers a pixel sample may draw before the next sample starts, which
+/// keeps the samples of a pixel from sharing numbers.
+const SAMPLE_STRIDE: u64 = 1 << 32;
+
+/// Bases of the Halton dimensions, further dimensions are drawn independently.
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+const ONE_MINUS_EPSILON: f64 = 1. - f64::EPSILON / 2.;
+
+/// Gives the numbers in `[0, 1)` from which the samples of a pixel are built.
+/// Samples read them one dimension after the other, and the values of a
+/// dimension are spread over the samples of the pixel as evenly as the sampler
+/// manages to.
+pub trait Sampler {
+    /// Goes back to the first dimension of the sample `index` of the pixel at
+    /// `(x, y)`.
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32);
+
+    fn get_1d(&mut self) -> f64;
+
+    /// Two dimensions spread together over the unit square.
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+/// Lets code drawing random numbers read the next dimensions of a sampler
+/// instead. Floats drawn from it are the sampler values, read in pairs of
+/// dimensions since they are mostly drawn two at a time, like the two angles
+/// of a direction.
+pub struct SamplerRng<'a> {
+    sampler: &'a mut dyn Sampler,
+    next_value: Option<f64>,
+}
+
+impl<'a> SamplerRng<'a> {
+    pub fn new(sampler: &'a mut dyn Sampler) -> Self {
+        SamplerRng {
+            sampler,
+            next_value: None,
+        }
+    }
+
+    fn next_value(&mut self) -> f64 {
+        match self.next_value.take() {
+            Some(value) => value,
+            None => {
+                let (value, next_value) = self.sampler.get_2d();
+                self.next_value = Some(next_value);
+                value
+            }
+        }
+    }
+}
+
+impl RngCore for SamplerRng<'_> {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_value() * 2f64.powi(32)) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        (self.next_value() * 2f64.powi(64)) as u64
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Generator of the sample `index` of a pixel, whatever the samples computed
+/// before.
+fn get_sample_rng(seed: u64, x: u32, y: u32, index: u32) -> Pcg32 {
+    let mut rng = Pcg32::for_pixel(seed, x, y);
+    rng.advance(u64::from(index) * SAMPLE_STRIDE);
+    rng
+}
+
+/// Seed specific to a dimension of a pixel.
+fn get_dimension_seed(seed: u64, (x, y): (u32, u32), dimension: u32) -> u64 {
+    hash(&[seed, u64::from(x), u64::from(y), u64::from(dimension)])
+}
+
+/// Uniform random numbers, without any care for their distribution.
+pub struct IndependentSampler {
+    seed: u64,
+    rng: Pcg32,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        IndependentSampler {
+            seed,
+            rng: get_sample_rng(seed, 0, 0, 0),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.rng = get_sample_rng(self.seed, x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.gen()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+/// Element at `index` of a random permutation of `[0, length)` picked with
+/// `seed`, computed without building the permutation (Kensler, "Correlated
+/// Multi-Jittered Sampling", 2013).
+fn permutation_element(mut index: u32, length: u32, seed: u32) -> u32 {
+    let mut mask = length - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    loop {
+        index ^= seed;
+        index = index.wrapping_mul(0xe170_893d);
+        index ^= seed >> 16;
+        index ^= (index & mask) >> 4;
+        index ^= seed >> 8;
+        index = index.wrapping_mul(0x0929_eb3f);
+        index ^= seed >> 23;
+        index ^= (index & mask) >> 1;
+        index = index.wrapping_mul(1 | seed >> 27);
+        index = index.wrapping_mul(0x6935_fa69);
+        index ^= (index & mask) >> 11;
+        index = index.wrapping_mul(0x74dc_b303);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0x9e50_1cc3);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0xc860_a3df);
+        index &= mask;
+        index ^= index >> 5;
+        if index < length {
+            return index.wrapping_add(seed) % length;
+        }
+    }
+}
+
+/// Splits each dimension in as many strata as there are samples in a pixel,
+/// and places each sample at a random position of a different stratum. Pairs
+/// of dimensions are split in a grid.
+pub struct StratifiedSampler {
+    seed: u64,
+    sample_count: u32,
+    grid_size: (u32, u32),
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+    rng: Pcg32,
+}
+
+impl StratifiedSampler {
+    pub fn new(seed: u64, samples_per_pixel: u32) -> Self {
+        let sample_count = samples_per_pixel.max(1);
+        let columns = ((sample_count as f64).sqrt() as u32).max(1);
+
+        StratifiedSampler {
+            seed,
+            sample_count,
+            grid_size: (columns, sample_count / columns),
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            rng: get_sample_rng(seed, 0, 0, 0),
+        }
+    }
+
+    /// Stratum of the current sample among `stratum_count` ones, which is
+    /// shuffled differently for each dimension.
+    fn next_stratum(&mut self, stratum_count: u32) -> u32 {
+        let seed = get_dimension_seed(self.seed, self.pixel, self.dimension) as u32;
+        permutation_element(self.index % stratum_count, stratum_count, seed)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+        self.rng = get_sample_rng(self.seed, x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let stratum = self.next_stratum(self.sample_count);
+        self.dimension += 1;
+
+        let jitter: f64 = self.rng.gen();
+        ((stratum as f64 + jitter) / self.sample_count as f64).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (columns, rows) = self.grid_size;
+        let stratum = self.next_stratum(columns * rows);
+        self.dimension += 2;
+
+        let (jitter_x, jitter_y): (f64, f64) = (self.rng.gen(), self.rng.gen());
+        (
+            (((stratum % columns) as f64 + jitter_x) / columns as f64).min(ONE_MINUS_EPSILON),
+            (((stratum / columns) as f64 + jitter_y) / rows as f64).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
+
+/// Mirrors the digits of `index` written in `base` around the decimal point,
+/// each digit being shuffled depending on the ones written before it (Owen
+/// scrambling).
+fn scrambled_radical_inverse(base: u32, mut index: u64, seed: u64) -> f64 {
+    let inverse_base = 1. / f64::from(base);
+    let base = u64::from(base);
+    let mut reversed_digits = 0u64;
+    let mut inverse_power = 1.;
+    while 1. - inverse_power < 1. {
+        let next = index / base;
+        let digit = (index - next * base) as u32;
+        let digit_seed = hash(&[seed, reversed_digits]) as u32;
+        let digit = permutation_element(digit, base as u32, digit_seed);
+        reversed_digits = reversed_digits * base + u64::from(digit);
+        inverse_power *= inverse_base;
+        index = next;
+    }
+    (reversed_digits as f64 * inverse_power).min(ONE_MINUS_EPSILON)
+}
+
+/// Halton sequence, one prime base per dimension, scrambled differently for
+/// each dimension of each pixel.
+pub struct HaltonSampler {
+    seed: u64,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+    rng: Pcg32,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        HaltonSampler {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            rng: get_sample_rng(seed, 0, 0, 0),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+        self.rng = get_sample_rng(self.seed, x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        let base = match PRIMES.get(dimension as usize) {
+            Some(base) => *base,
+            None => return self.rng.gen(),
+        };
+
+        let seed = get_dimension_seed(self.seed, self.pixel, dimension);
+        scrambled_radical_inverse(base, u64::from(self.index), seed)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+/// Second dimension of the Sobol sequence, as bits following the decimal
+/// point. The first one is the index with its bits reversed.
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut direction = 1u32 << 31;
+    let mut value = 0;
+    while index != 0 {
+        if index & 1 == 1 {
+            value ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    value
+}
+
+/// Random permutation of the values sharing their higher bits, for every
+/// group of them (Burley, "Practical Hash-based Owen Scrambling", 2020).
+fn nested_uniform_scramble(value: u32, seed: u32) -> u32 {
+    let mut value = value.reverse_bits().wrapping_add(seed);
+    value ^= value.wrapping_mul(0x6c50_b47c);
+    value ^= value.wrapping_mul(0xb82f_1e52);
+    value ^= value.wrapping_mul(0xc7af_e638);
+    value ^= value.wrapping_mul(0x8d22_f6e6);
+    value.reverse_bits()
+}
+
+/// First two dimensions of the Sobol sequence with Owen scrambling, reused for
+/// every pair of dimensions with the samples shuffled differently, which keeps
+/// each pair well spread (Burley, 2020).
+pub struct SobolSampler {
+    seed: u64,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        SobolSampler {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn next_dimensions(&mut self, count: u32) -> (u32, u32, u32) {
+        let seed = get_dimension_seed(self.seed, self.pixel, self.dimension);
+        self.dimension += count;
+
+        let index = nested_uniform_scramble(self.index, seed as u32);
+        let (first_seed, second_seed) = ((seed >> 32) as u32, hash(&[seed]) as u32);
+        (index, first_seed, second_seed)
+    }
+}
+
+fn bits_to_unit(bits: u32) -> f64 {
+    f64::from(bits) / 2f64.powi(32)
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (index, seed, _) = self.next_dimensions(1);
+        bits_to_unit(nested_uniform_scramble(index.reverse_bits(), seed))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (index, first_seed, second_seed) = self.next_dimensions(2);
+        (
+            bits_to_unit(nested_uniform_scramble(index.reverse_bits(), first_seed)),
+            bits_to_unit(nested_uniform_scramble(
+                sobol_second_dimension(index),
+                second_seed,
+            )),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_samplers(samples_per_pixel: u32) -> Vec<Box<dyn Sampler>> {
+        vec![
+            Box::new(IndependentSampler::new(3)),
+            Box::new(StratifiedSampler::new(3, samples_per_pixel)),
+            Box::new(HaltonSampler::new(3)),
+            Box::new(SobolSampler::new(3)),
+        ]
+    }
+
+    /// Cells of a `size` by `size` grid holding each of the 2D samples of a
+    /// pixel, read at the dimension `dimension`.
+    fn get_cells(sampler: &mut dyn Sampler, dimension: u32, size: u32) -> Vec<u32> {
+        let mut cells: Vec<u32> = (0..size * size)
+            .map(|index| {
+                sampler.start_pixel_sample(5, 2, index);
+                for _ in 0..dimension {
+                    sampler.get_1d();
+                }
+                let (x, y) = sampler.get_2d();
+                (y * size as f64) as u32 * size + (x * size as f64) as u32
+            })
+            .collect();
+        cells.sort_unstable();
+        cells
+    }
+
+    /// Mean squared error of the estimates of the integral of `x * y` over
+    /// the unit square, one per pixel.
+    fn get_integration_error(sampler: &mut dyn Sampler, sample_count: u32) -> f64 {
+        let pixel_count = 64;
+        let squared_errors: f64 = (0..pixel_count)
+            .map(|x| {
+                let sum: f64 = (0..sample_count)
+                    .map(|index| {
+                        sampler.start_pixel_sample(x, 0, index);
+                        let (u, v) = sampler.get_2d();
+                        u * v
+                    })
+                    .sum();
+                (sum / sample_count as f64 - 0.25).powi(2)
+            })
+            .sum();
+        squared_errors / pixel_count as f64
+    }
+
+    #[test]
+    fn samples_are_in_unit_range() {
+        for mut sampler in get_samplers(10) {
+            for index in 0..10 {
+                sampler.start_pixel_sample(1, 2, index);
+                for _ in 0..50 {
+                    let (x, y) = sampler.get_2d();
+                    for value in &[sampler.get_1d(), x, y] {
+                        assert!((0. ..1.).contains(value));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn samples_can_be_computed_again() {
+        for mut sampler in get_samplers(8) {
+            sampler.start_pixel_sample(4, 1, 6);
+            let first = (sampler.get_1d(), sampler.get_2d());
+            sampler.start_pixel_sample(0, 0, 0);
+            sampler.get_2d();
+            sampler.start_pixel_sample(4, 1, 6);
+
+            assert_eq!(first, (sampler.get_1d(), sampler.get_2d()));
+        }
+    }
+
+    #[test]
+    fn permutation_elements_form_a_permutation() {
+        for length in &[1, 5, 16, 100] {
+            let mut elements: Vec<u32> = (0..*length)
+                .map(|index| permutation_element(index, *length, 1234))
+                .collect();
+            elements.sort_unstable();
+
+            assert_eq!((0..*length).collect::<Vec<u32>>(), elements);
+        }
+    }
+
+    #[test]
+    fn scrambled_radical_inverse_keeps_the_strata_of_the_digits() {
+        let mut strata: Vec<u32> = (0..9)
+            .map(|index| (scrambled_radical_inverse(3, index, 17) * 9.) as u32)
+            .collect();
+        strata.sort_unstable();
+
+        assert_eq!((0..9).collect::<Vec<u32>>(), strata);
+        assert_ne!(
+            scrambled_radical_inverse(3, 5, 17),
+            scrambled_radical_inverse(3, 5, 18)
+        );
+    }
+
+    #[test]
+    fn stratified_and_sobol_samples_fill_every_cell() {
+        let every_cell: Vec<u32> = (0..16).collect();
+        let mut samplers: Vec<Box<dyn Sampler>> = vec![
+            Box::new(StratifiedSampler::new(3, 16)),
+            Box::new(SobolSampler::new(3)),
+        ];
+
+        for sampler in &mut samplers {
+            for dimension in &[0, 2, 7] {
+                assert_eq!(every_cell, get_cells(sampler.as_mut(), *dimension, 4));
+            }
+        }
+    }
+
+    #[test]
+    fn halton_samples_are_stratified_in_the_first_dimension() {
+        let mut sampler = HaltonSampler::new(3);
+
+        let mut strata: Vec<u32> = (0..16)
+            .map(|index| {
+                sampler.start_pixel_sample(5, 2, index);
+                (sampler.get_1d() * 16.) as u32
+            })
+            .collect();
+        strata.sort_unstable();
+
+        assert_eq!((0..16).collect::<Vec<u32>>(), strata);
+    }
+
+    #[test]
+    fn low_discrepancy_samplers_converge_faster() {
+        let independent_error = get_integration_error(&mut IndependentSampler::new(3), 64);
+
+        for mut sampler in get_samplers(64).into_iter().skip(1) {
+            let error = get_integration_error(sampler.as_mut(), 64);
+            assert!(
+                error * 5. < independent_error,
+                "{} against {}",
+                error,
+                independent_error
+            );
+        }
+    }
+
+    #[test]
+    fn random_numbers_drawn_from_a_sampler_are_its_values_in_pairs() {
+        let mut sampler = SobolSampler::new(3);
+        sampler.start_pixel_sample(1, 1, 3);
+        let expected = sampler.get_2d();
+
+        sampler.start_pixel_sample(1, 1, 3);
+        let mut rng = SamplerRng::new(&mut sampler);
+        let drawn: (f64, f64) = (rng.gen_range(0., 1.), rng.gen_range(0., 1.));
+
+        assert!((drawn.0 - expected.0).abs() < 1e-12);
+        assert!((drawn.1 - expected.1).abs() < 1e-12);
+    }
+}
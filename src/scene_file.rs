@@ -0,0 +1,1260 @@
+use crate::bvh::BvhNode;
+use crate::camera::Camera;
+use crate::hit::{BoxShape, Hittable, HittableList, MovingSphere, Sphere};
+use crate::material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal};
+use crate::medium::{Atmosphere, ConstantMedium};
+use crate::mesh::Triangle;
+use crate::obj;
+use crate::perlin::Perlin;
+use crate::radiance::Radiance;
+use crate::scenes::{Background, Scene};
+use crate::texture::{
+    CheckerTexture, ConstantTexture, FbmTexture, ImageTexture, MarbleTexture, NoiseTexture,
+    Texture, WoodTexture,
+};
+use crate::transform::{Transform, Transformed};
+use crate::vector3::Vector3;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use toml::Spanned;
+
+type Degrees = f64;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(error) => write!(f, "{}", error),
+            SceneError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(error: io::Error) -> Self {
+        SceneError::Io(error)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDescription {
+    look_from: [f64; 3],
+    look_at: [f64; 3],
+    #[serde(default = "default_up_vector")]
+    up_vector: [f64; 3],
+    vertical_fov: Degrees,
+    aspect_ratio: Option<f64>,
+    #[serde(default)]
+    aperture: f64,
+    focus_distance: Option<f64>,
+    #[serde(default)]
+    shutter_open: f64,
+    #[serde(default)]
+    shutter_close: f64,
+}
+
+fn default_up_vector() -> [f64; 3] {
+    [0., 1., 0.]
+}
+
+/// Either a color or the name of a texture.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AlbedoDescription {
+    Color([f64; 3]),
+    Texture(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDescription {
+    Constant {
+        color: [f64; 3],
+    },
+    Checker {
+        odd: [f64; 3],
+        even: [f64; 3],
+        #[serde(default = "default_checker_frequency")]
+        frequency: f64,
+    },
+    Image {
+        path: String,
+    },
+    Noise {
+        scale: f64,
+        #[serde(default)]
+        seed: u64,
+    },
+    Marble {
+        scale: f64,
+        #[serde(default = "default_marble_turbulence")]
+        turbulence: f64,
+        #[serde(default)]
+        seed: u64,
+    },
+    Wood {
+        scale: f64,
+        light: [f64; 3],
+        dark: [f64; 3],
+        #[serde(default)]
+        seed: u64,
+    },
+    Fbm {
+        scale: f64,
+        #[serde(default = "default_fbm_octaves")]
+        octaves: u32,
+        #[serde(default)]
+        seed: u64,
+    },
+}
+
+fn default_end_time() -> f64 {
+    1.
+}
+
+fn default_checker_frequency() -> f64 {
+    10.
+}
+
+fn default_marble_turbulence() -> f64 {
+    10.
+}
+
+fn default_fbm_octaves() -> u32 {
+    6
+}
+
+fn get_perlin(seed: u64) -> Perlin {
+    Perlin::new(&mut StdRng::seed_from_u64(seed))
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDescription {
+    Lambertian {
+        albedo: AlbedoDescription,
+    },
+    Metal {
+        albedo: AlbedoDescription,
+        fuzziness: f64,
+    },
+    Dielectric {
+        refraction_index: f64,
+    },
+    DiffuseLight {
+        emit: [f64; 3],
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDescription {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    MovingSphere {
+        center_0: [f64; 3],
+        center_1: [f64; 3],
+        #[serde(default)]
+        time_0: f64,
+        #[serde(default = "default_end_time")]
+        time_1: f64,
+        radius: f64,
+        material: String,
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        material: String,
+    },
+    Obj {
+        path: String,
+    },
+    ConstantMedium {
+        boundary: BoundaryDescription,
+        density: f64,
+        albedo: AlbedoDescription,
+    },
+    /// Places a shape after scaling it, rotating it around X, Y then Z, and
+    /// translating it.
+    Instance {
+        shape: String,
+        #[serde(default = "default_scale")]
+        scale: ScaleDescription,
+        #[serde(default)]
+        rotate: [Degrees; 3],
+        #[serde(default)]
+        translate: [f64; 3],
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScaleDescription {
+    Uniform(f64),
+    PerAxis([f64; 3]),
+}
+
+fn default_scale() -> ScaleDescription {
+    ScaleDescription::Uniform(1.)
+}
+
+/// Closed shape holding a medium.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum BoundaryDescription {
+    Sphere { center: [f64; 3], radius: f64 },
+    Box { min: [f64; 3], max: [f64; 3] },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AtmosphereDescription {
+    density: f64,
+    #[serde(default = "default_atmosphere_albedo")]
+    albedo: [f64; 3],
+}
+
+fn default_atmosphere_albedo() -> [f64; 3] {
+    [1., 1., 1.]
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDescription {
+    camera: Spanned<CameraDescription>,
+    background: Option<[f64; 3]>,
+    atmosphere: Option<Spanned<AtmosphereDescription>>,
+    #[serde(default)]
+    textures: HashMap<String, Spanned<TextureDescription>>,
+    #[serde(default)]
+    shapes: HashMap<String, Spanned<Vec<Spanned<ObjectDescription>>>>,
+    #[serde(default)]
+    materials: HashMap<String, Spanned<MaterialDescription>>,
+    #[serde(default)]
+    objects: Vec<Spanned<ObjectDescription>>,
+}
+
+fn to_vector([x, y, z]: [f64; 3]) -> Vector3 {
+    Vector3::from((x, y, z))
+}
+
+fn line_at(content: &str, span: &Range<usize>) -> usize {
+    content[..span.start].matches('\n').count() + 1
+}
+
+type Textures = HashMap<String, Arc<dyn Texture>>;
+type Shapes = HashMap<String, Shape>;
+
+/// Objects defined once and placed by instances, with the emitting ones also
+/// gathered apart to be placed among the lights.
+struct Shape {
+    object: Arc<dyn Hittable>,
+    lights: Option<Arc<dyn Hittable>>,
+}
+
+#[derive(Default)]
+struct SceneObjects {
+    objects: Vec<Box<dyn Hittable>>,
+    lights: Vec<Box<dyn Hittable>>,
+}
+
+impl SceneObjects {
+    fn push(&mut self, object: Box<dyn Hittable>) {
+        self.objects.push(object);
+    }
+
+    fn push_light(&mut self, light: Box<dyn Hittable>) {
+        let light: Arc<dyn Hittable> = Arc::from(light);
+        self.objects.push(Box::new(light.clone()));
+        self.lights.push(Box::new(light));
+    }
+
+    fn push_maybe_light(&mut self, object: Box<dyn Hittable>, emits: bool) {
+        if emits {
+            self.push_light(object)
+        } else {
+            self.push(object)
+        }
+    }
+}
+
+fn get_instance_transform(
+    scale: &ScaleDescription,
+    [x, y, z]: [Degrees; 3],
+    translate: [f64; 3],
+) -> Option<Transform> {
+    let factors = match *scale {
+        ScaleDescription::Uniform(factor) => Vector3::from((factor, factor, factor)),
+        ScaleDescription::PerAxis(factors) => to_vector(factors),
+    };
+    if factors.x == 0. || factors.y == 0. || factors.z == 0. {
+        return None;
+    }
+
+    Some(
+        Transform::scaling(factors)
+            .then(&Transform::rotation(Vector3::from((1., 0., 0.)), x))
+            .then(&Transform::rotation(Vector3::from((0., 1., 0.)), y))
+            .then(&Transform::rotation(Vector3::from((0., 0., 1.)), z))
+            .then(&Transform::translation(to_vector(translate))),
+    )
+}
+
+impl BoundaryDescription {
+    /// The material of a boundary is never used for shading.
+    fn to_hittable(&self, material: Box<dyn Material>) -> Box<dyn Hittable> {
+        match self {
+            BoundaryDescription::Sphere { center, radius } => {
+                Box::new(Sphere::new(to_vector(*center), *radius, material))
+            }
+            BoundaryDescription::Box { min, max } => {
+                Box::new(BoxShape::new(to_vector(*min), to_vector(*max), material))
+            }
+        }
+    }
+}
+
+impl TextureDescription {
+    fn to_texture(&self, directory: &Path) -> Result<Arc<dyn Texture>, String> {
+        match self {
+            TextureDescription::Constant { color } => {
+                Ok(Arc::new(ConstantTexture::new(to_vector(*color))))
+            }
+            TextureDescription::Checker {
+                odd,
+                even,
+                frequency,
+            } => Ok(Arc::new(CheckerTexture {
+                odd: Arc::new(ConstantTexture::new(to_vector(*odd))),
+                even: Arc::new(ConstantTexture::new(to_vector(*even))),
+                frequency: *frequency,
+            })),
+            TextureDescription::Image { path } => ImageTexture::load(&directory.join(path))
+                .map(|texture| Arc::new(texture) as Arc<dyn Texture>)
+                .map_err(|error| format!("cannot load '{}': {}", path, error)),
+            TextureDescription::Noise { scale, seed } => Ok(Arc::new(NoiseTexture {
+                noise: get_perlin(*seed),
+                scale: *scale,
+            })),
+            TextureDescription::Marble {
+                scale,
+                turbulence,
+                seed,
+            } => Ok(Arc::new(MarbleTexture {
+                noise: get_perlin(*seed),
+                scale: *scale,
+                turbulence: *turbulence,
+            })),
+            TextureDescription::Wood {
+                scale,
+                light,
+                dark,
+                seed,
+            } => Ok(Arc::new(WoodTexture {
+                noise: get_perlin(*seed),
+                scale: *scale,
+                light: to_vector(*light),
+                dark: to_vector(*dark),
+            })),
+            TextureDescription::Fbm {
+                scale,
+                octaves,
+                seed,
+            } => Ok(Arc::new(FbmTexture {
+                noise: get_perlin(*seed),
+                scale: *scale,
+                octaves: *octaves,
+            })),
+        }
+    }
+}
+
+impl AlbedoDescription {
+    fn to_texture(&self, textures: &Textures) -> Result<Arc<dyn Texture>, String> {
+        match self {
+            AlbedoDescription::Color(color) => {
+                Ok(Arc::new(ConstantTexture::new(to_vector(*color))))
+            }
+            AlbedoDescription::Texture(name) => textures
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("unknown texture '{}'", name)),
+        }
+    }
+}
+
+impl MaterialDescription {
+    fn to_material(&self, textures: &Textures) -> Result<Box<dyn Material>, String> {
+        Ok(match self {
+            MaterialDescription::Lambertian { albedo } => Box::new(Lambertian {
+                albedo: albedo.to_texture(textures)?,
+            }),
+            MaterialDescription::Metal { albedo, fuzziness } => Box::new(Metal {
+                albedo: albedo.to_texture(textures)?,
+                fuzziness: *fuzziness,
+            }),
+            MaterialDescription::Dielectric { refraction_index } => Box::new(Dielectric {
+                refraction_index: *refraction_index,
+            }),
+            MaterialDescription::DiffuseLight { emit: [r, g, b] } => Box::new(DiffuseLight {
+                emit: Radiance::new(*r, *g, *b),
+            }),
+        })
+    }
+}
+
+impl CameraDescription {
+    fn to_camera(&self, (width, height): (u32, u32)) -> Camera {
+        let look_from = to_vector(self.look_from);
+        let look_at = to_vector(self.look_at);
+
+        Camera::new(
+            look_from,
+            look_at,
+            to_vector(self.up_vector),
+            self.vertical_fov,
+            self.aspect_ratio.unwrap_or(width as f64 / height as f64),
+            self.aperture,
+            self.focus_distance
+                .unwrap_or_else(|| (look_from - look_at).norm()),
+        )
+        .with_shutter(self.shutter_open, self.shutter_close)
+    }
+}
+
+/// Parses a TOML scene description. The camera aspect ratio defaults to the one
+/// of `geometry`, the background to the sky, and OBJ files are looked for
+/// relative to `directory`.
+pub fn parse_scene(
+    content: &str,
+    geometry: (u32, u32),
+    directory: &Path,
+) -> Result<Scene, SceneError> {
+    let description: SceneDescription =
+        toml::from_str(content).map_err(|error| SceneError::Parse {
+            line: error.span().map_or(1, |span| line_at(content, &span)),
+            message: error.message().to_string(),
+        })?;
+
+    let camera = description.camera.get_ref();
+    if camera.shutter_open > camera.shutter_close {
+        return Err(SceneError::Parse {
+            line: line_at(content, &description.camera.span()),
+            message: String::from("shutter_open must not be after shutter_close"),
+        });
+    }
+    let view_direction = to_vector(camera.look_at) - to_vector(camera.look_from);
+    if view_direction.squared_norm() == 0. {
+        return Err(SceneError::Parse {
+            line: line_at(content, &description.camera.span()),
+            message: String::from("look_from and look_at must differ"),
+        });
+    }
+    if to_vector(camera.up_vector)
+        .cross(&view_direction)
+        .squared_norm()
+        == 0.
+    {
+        return Err(SceneError::Parse {
+            line: line_at(content, &description.camera.span()),
+            message: String::from("up_vector must not be zero or along the view direction"),
+        });
+    }
+
+    let mut textures = Textures::new();
+    for (name, texture) in &description.textures {
+        let texture =
+            texture
+                .get_ref()
+                .to_texture(directory)
+                .map_err(|message| SceneError::Parse {
+                    line: line_at(content, &texture.span()),
+                    message,
+                })?;
+        textures.insert(name.clone(), texture);
+    }
+
+    let get_material = |name: &str, span: &Range<usize>| {
+        let material = description
+            .materials
+            .get(name)
+            .ok_or_else(|| SceneError::Parse {
+                line: line_at(content, span),
+                message: format!("unknown material '{}'", name),
+            })?;
+        material
+            .get_ref()
+            .to_material(&textures)
+            .map_err(|message| SceneError::Parse {
+                line: line_at(content, &material.span()),
+                message,
+            })
+    };
+
+    let emits = |name: &str| {
+        description.materials.get(name).is_some_and(|material| {
+            matches!(material.get_ref(), MaterialDescription::DiffuseLight { .. })
+        })
+    };
+
+    let add_object = |objects: &mut SceneObjects,
+                      object: &ObjectDescription,
+                      span: Range<usize>,
+                      shapes: &Shapes|
+     -> Result<(), SceneError> {
+        match object {
+            ObjectDescription::Sphere {
+                center,
+                radius,
+                material,
+            } => objects.push_maybe_light(
+                Box::new(Sphere::new(
+                    to_vector(*center),
+                    *radius,
+                    get_material(material, &span)?,
+                )),
+                emits(material),
+            ),
+            ObjectDescription::MovingSphere {
+                center_0,
+                center_1,
+                time_0,
+                time_1,
+                radius,
+                material,
+            } => {
+                if time_0 >= time_1 {
+                    return Err(SceneError::Parse {
+                        line: line_at(content, &span),
+                        message: String::from("time_0 must be before time_1"),
+                    });
+                }
+                objects.push(Box::new(MovingSphere::new(
+                    (to_vector(*center_0), *time_0),
+                    (to_vector(*center_1), *time_1),
+                    *radius,
+                    get_material(material, &span)?,
+                )))
+            }
+            ObjectDescription::Triangle { vertices, material } => objects.push_maybe_light(
+                Box::new(Triangle::new(
+                    to_vector(vertices[0]),
+                    to_vector(vertices[1]),
+                    to_vector(vertices[2]),
+                    get_material(material, &span)?,
+                )),
+                emits(material),
+            ),
+            ObjectDescription::Obj { path } => {
+                let meshes =
+                    obj::load_obj(&directory.join(path)).map_err(|error| SceneError::Parse {
+                        line: line_at(content, &span),
+                        message: format!("cannot load '{}': {}", path, error),
+                    })?;
+                for mesh in meshes.into_objects() {
+                    objects.push(mesh);
+                }
+            }
+            ObjectDescription::ConstantMedium {
+                boundary,
+                density,
+                albedo,
+            } => {
+                let to_error = |message| SceneError::Parse {
+                    line: line_at(content, &span),
+                    message,
+                };
+                if *density <= 0. {
+                    return Err(to_error(String::from("density must be positive")));
+                }
+                let albedo = albedo.to_texture(&textures).map_err(to_error)?;
+                let phase_function = || -> Box<dyn Material> {
+                    Box::new(Isotropic {
+                        albedo: albedo.clone(),
+                    })
+                };
+                objects.push(Box::new(ConstantMedium::new(
+                    boundary.to_hittable(phase_function()),
+                    *density,
+                    phase_function(),
+                )))
+            }
+            ObjectDescription::Instance {
+                shape,
+                scale,
+                rotate,
+                translate,
+            } => {
+                let to_error = |message| SceneError::Parse {
+                    line: line_at(content, &span),
+                    message,
+                };
+                let shape = shapes
+                    .get(shape)
+                    .ok_or_else(|| to_error(format!("unknown shape '{}'", shape)))?;
+                let transform = get_instance_transform(scale, *rotate, *translate)
+                    .ok_or_else(|| to_error(String::from("scale factors must not be null")))?;
+                objects.push(Box::new(Transformed::new(shape.object.clone(), transform)));
+                if let Some(lights) = &shape.lights {
+                    objects
+                        .lights
+                        .push(Box::new(Transformed::new(lights.clone(), transform)));
+                }
+            }
+        }
+        Ok(())
+    };
+
+    let mut shapes = Shapes::new();
+    for (name, shape) in &description.shapes {
+        let mut shape_objects = SceneObjects::default();
+        for object in shape.get_ref() {
+            add_object(
+                &mut shape_objects,
+                object.get_ref(),
+                object.span(),
+                &Shapes::new(),
+            )?;
+        }
+        let SceneObjects {
+            mut objects,
+            lights,
+        } = shape_objects;
+        let object: Arc<dyn Hittable> = match objects.len() {
+            0 => {
+                return Err(SceneError::Parse {
+                    line: line_at(content, &shape.span()),
+                    message: format!("empty shape '{}'", name),
+                })
+            }
+            1 => Arc::from(objects.pop().unwrap()),
+            _ => Arc::new(BvhNode::new(HittableList::new(objects))),
+        };
+        let lights: Option<Arc<dyn Hittable>> = if lights.is_empty() {
+            None
+        } else {
+            Some(Arc::new(HittableList::new(lights)))
+        };
+        shapes.insert(name.clone(), Shape { object, lights });
+    }
+
+    let mut objects = SceneObjects::default();
+    for object in &description.objects {
+        add_object(&mut objects, object.get_ref(), object.span(), &shapes)?;
+    }
+
+    let atmosphere = match &description.atmosphere {
+        Some(atmosphere) if atmosphere.get_ref().density <= 0. => {
+            return Err(SceneError::Parse {
+                line: line_at(content, &atmosphere.span()),
+                message: String::from("density must be positive"),
+            })
+        }
+        Some(atmosphere) => Some(Atmosphere {
+            density: atmosphere.get_ref().density,
+            albedo: to_vector(atmosphere.get_ref().albedo),
+        }),
+        None => None,
+    };
+
+    let background = match description.background {
+        Some([r, g, b]) => Background::Uniform(Radiance::new(r, g, b)),
+        None => Background::Sky,
+    };
+
+    Ok(Scene {
+        world: HittableList::new(objects.objects),
+        lights: HittableList::new(objects.lights),
+        camera: camera.to_camera(geometry),
+        background,
+        atmosphere,
+    })
+}
+
+pub fn load_scene(path: &Path, geometry: (u32, u32)) -> Result<Scene, SceneError> {
+    let content = fs::read_to_string(path)?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    parse_scene(&content, geometry, directory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Pcg32;
+    use crate::ray::Ray;
+
+    const CAMERA: &str = "
+[camera]
+look_from = [0, 0, 0]
+look_at = [0, 0, -1]
+vertical_fov = 90
+";
+
+    fn parse(content: &str) -> Result<Scene, SceneError> {
+        parse_scene(content, (100, 100), Path::new(""))
+    }
+
+    #[test]
+    fn parses_camera_only_scene() {
+        let scene = parse(CAMERA).unwrap();
+
+        assert!(scene.world.into_objects().is_empty());
+    }
+
+    #[test]
+    fn parses_objects_with_named_materials() {
+        let mut rng = Pcg32::new(0, 0);
+        let content = format!(
+            "{}
+[materials.red]
+type = \"lambertian\"
+albedo = [1, 0, 0]
+
+[[objects]]
+type = \"sphere\"
+center = [0, 0, -2]
+radius = 0.5
+material = \"red\"
+
+[[objects]]
+type = \"triangle\"
+vertices = [[-1, -1, -5], [1, -1, -5], [0, 1, -5]]
+material = \"red\"
+",
+            CAMERA
+        );
+
+        let scene = parse(&content).unwrap();
+        let hit = scene
+            .world
+            .hit(&scene.camera.get_ray(0.5, 0.5, &mut rng), 0., 10.)
+            .unwrap();
+
+        assert_eq!(Vector3::from((0., 0., -1.5)), hit.point);
+        assert_eq!(2, scene.world.into_objects().len());
+    }
+
+    #[test]
+    fn hits_triangle_behind_sphere() {
+        let content = format!(
+            "{}
+[materials.glass]
+type = \"dielectric\"
+refraction_index = 1.5
+
+[[objects]]
+type = \"triangle\"
+vertices = [[-1, -1, -5], [1, -1, -5], [0, 1, -5]]
+material = \"glass\"
+",
+            CAMERA
+        );
+
+        let world = parse(&content).unwrap().world;
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+
+        assert_eq!(
+            Vector3::from((0., 0., -5.)),
+            world.hit(&ray, 0., 10.).unwrap().point
+        );
+    }
+
+    #[test]
+    fn parses_light_materials() {
+        let content = format!(
+            "{}
+[materials.lamp]
+type = \"diffuse_light\"
+emit = [4, 4, 4]
+
+[[objects]]
+type = \"sphere\"
+center = [0, 0, -2]
+radius = 0.5
+material = \"lamp\"
+",
+            CAMERA
+        );
+
+        let world = parse(&content).unwrap().world;
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+        let hit = world.hit(&ray, 0., 10.).unwrap();
+
+        assert_eq!(Radiance::new(4., 4., 4.), hit.material.emitted(&hit));
+    }
+
+    #[test]
+    fn parses_textured_materials() {
+        let mut rng = Pcg32::new(0, 0);
+        let content = format!(
+            "{}
+[textures.checker]
+type = \"checker\"
+odd = [0, 0, 0]
+even = [1, 1, 1]
+
+[materials.ground]
+type = \"lambertian\"
+albedo = \"checker\"
+
+[[objects]]
+type = \"sphere\"
+center = [0, 0, -2]
+radius = 0.5
+material = \"ground\"
+",
+            CAMERA
+        );
+
+        let world = parse(&content).unwrap().world;
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+        let hit = world.hit(&ray, 0., 10.).unwrap();
+
+        assert!(hit.material.scatter(&ray, &hit, &mut rng).is_some());
+    }
+
+    #[test]
+    fn parses_procedural_textures() {
+        let content = format!(
+            "{}
+[textures.marble]
+type = \"marble\"
+scale = 4
+
+[textures.wood]
+type = \"wood\"
+scale = 8
+light = [0.8, 0.6, 0.4]
+dark = [0.4, 0.2, 0.1]
+seed = 3
+
+[textures.clouds]
+type = \"fbm\"
+scale = 2
+octaves = 4
+
+[textures.noise]
+type = \"noise\"
+scale = 1
+",
+            CAMERA
+        );
+
+        assert!(parse(&content).is_ok());
+    }
+
+    #[test]
+    fn reports_line_of_material_with_unknown_texture() {
+        let content = format!(
+            "{}
+[materials.ground]
+type = \"metal\"
+albedo = \"marble\"
+fuzziness = 0
+
+[[objects]]
+type = \"sphere\"
+center = [0, 0, -2]
+radius = 0.5
+material = \"ground\"
+",
+            CAMERA
+        );
+
+        let error = parse(&content).err().unwrap();
+
+        assert_eq!("line 7: unknown texture 'marble'", error.to_string());
+    }
+
+    #[test]
+    fn reports_missing_image_texture() {
+        let content = format!(
+            "{}
+[textures.earth]
+type = \"image\"
+path = \"missing.ppm\"
+",
+            CAMERA
+        );
+
+        let error = parse(&content).err().unwrap();
+
+        assert!(error
+            .to_string()
+            .starts_with("line 7: cannot load 'missing.ppm'"));
+    }
+
+    #[test]
+    fn parses_uniform_background() {
+        let content = format!("background = [0, 0, 0]\n{}", CAMERA);
+
+        let scene = parse(&content).unwrap();
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 1., 0.)));
+
+        assert_eq!(Radiance::default(), scene.background.radiance(&ray));
+    }
+
+    #[test]
+    fn parses_moving_spheres_and_shutter() {
+        let mut rng = Pcg32::new(0, 0);
+        let content = "
+[camera]
+look_from = [0, 0, 0]
+look_at = [0, 0, -1]
+vertical_fov = 90
+shutter_open = 0.5
+shutter_close = 0.5
+
+[materials.red]
+type = \"lambertian\"
+albedo = [1, 0, 0]
+
+[[objects]]
+type = \"moving_sphere\"
+center_0 = [0, -1, -2]
+center_1 = [0, 1, -2]
+radius = 0.5
+material = \"red\"
+";
+
+        let scene = parse(content).unwrap();
+        let hit = scene
+            .world
+            .hit(&scene.camera.get_ray(0.5, 0.5, &mut rng), 0., 10.)
+            .unwrap();
+
+        assert_eq!(Vector3::from((0., 0., -1.5)), hit.point);
+    }
+
+    #[test]
+    fn reports_reversed_moving_sphere_times() {
+        let content = format!(
+            "{}
+[[objects]]
+type = \"moving_sphere\"
+center_0 = [0, 0, 0]
+center_1 = [0, 1, 0]
+time_0 = 1
+time_1 = 0
+radius = 1
+material = \"red\"
+",
+            CAMERA
+        );
+
+        let error = parse(&content).err().unwrap();
+
+        assert_eq!("line 7: time_0 must be before time_1", error.to_string());
+    }
+
+    #[test]
+    fn reports_reversed_shutter_interval() {
+        let content = format!("{}shutter_open = 1\nshutter_close = 0\n", CAMERA);
+
+        let error = parse(&content).err().unwrap();
+
+        assert_eq!(
+            "line 2: shutter_open must not be after shutter_close",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn reports_camera_looking_at_itself() {
+        let content = "[camera]\nlook_from = [1, 2, 3]\nlook_at = [1, 2, 3]\nvertical_fov = 90\n";
+
+        let error = parse(content).err().unwrap();
+
+        assert_eq!(
+            "line 1: look_from and look_at must differ",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn reports_zero_or_parallel_up_vector() {
+        for up_vector in &["[0, 0, 0]", "[0, 0, 2]"] {
+            let content = format!("{}up_vector = {}\n", CAMERA, up_vector);
+
+            let error = parse(&content).err().unwrap();
+
+            assert_eq!(
+                "line 2: up_vector must not be zero or along the view direction",
+                error.to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn parses_constant_media() {
+        let content = format!(
+            "{}
+[[objects]]
+type = \"constant_medium\"
+density = 1e9
+albedo = [0.5, 0.5, 0.5]
+boundary = {{ type = \"box\", min = [-1, -1, -3], max = [1, 1, -2] }}
+",
+            CAMERA
+        );
+
+        let world = parse(&content).unwrap().world;
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+        let hit = world
+            .sample_hit(&ray, 0., 10., &mut Pcg32::new(0, 0))
+            .unwrap();
+
+        assert!((hit.t - 2.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn parses_atmosphere() {
+        let content = format!("{}\n[atmosphere]\ndensity = 0.01\n", CAMERA);
+
+        let atmosphere = parse(&content).unwrap().atmosphere.unwrap();
+
+        assert_eq!(0.01, atmosphere.density);
+        assert_eq!(Vector3::from((1., 1., 1.)), atmosphere.albedo);
+    }
+
+    #[test]
+    fn reports_non_positive_medium_density() {
+        let content = format!(
+            "{}
+[[objects]]
+type = \"constant_medium\"
+density = 0
+albedo = [0.5, 0.5, 0.5]
+boundary = {{ type = \"sphere\", center = [0, 0, 0], radius = 1 }}
+",
+            CAMERA
+        );
+
+        let error = parse(&content).err().unwrap();
+
+        assert_eq!("line 7: density must be positive", error.to_string());
+    }
+
+    #[test]
+    fn places_instances_of_shared_shapes() {
+        let content = format!(
+            "{}
+[materials.red]
+type = \"lambertian\"
+albedo = [1, 0, 0]
+
+[[shapes.ball]]
+type = \"sphere\"
+center = [0, 0, 0]
+radius = 1
+material = \"red\"
+
+[[objects]]
+type = \"instance\"
+shape = \"ball\"
+scale = 0.5
+translate = [0, 0, -2]
+
+[[objects]]
+type = \"instance\"
+shape = \"ball\"
+scale = [1, 1, 2]
+rotate = [0, 90, 0]
+translate = [0, 0, -10]
+",
+            CAMERA
+        );
+
+        let world = parse(&content).unwrap().world;
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+        let hit = world.hit(&ray, 0., 100.).unwrap();
+        let behind_ray = Ray::new(Vector3::from((1.5, 0., 0.)), Vector3::from((0., 0., -1.)));
+        let behind_hit = world.hit(&behind_ray, 0., 100.).unwrap();
+
+        assert!((hit.t - 1.5).abs() < 1e-9);
+        let expected_z = -10. + (1. - 0.75f64.powi(2)).sqrt();
+        assert!((behind_hit.point.z - expected_z).abs() < 1e-6);
+        assert_eq!(2, world.into_objects().len());
+    }
+
+    #[test]
+    fn gathers_emitting_objects_and_instances_among_lights() {
+        let content = format!(
+            "{}
+[materials.lamp]
+type = \"diffuse_light\"
+emit = [4, 4, 4]
+
+[materials.red]
+type = \"lambertian\"
+albedo = [1, 0, 0]
+
+[[shapes.lamp_post]]
+type = \"sphere\"
+center = [0, 2, 0]
+radius = 0.5
+material = \"lamp\"
+
+[[shapes.lamp_post]]
+type = \"sphere\"
+center = [0, 0, 0]
+radius = 1
+material = \"red\"
+
+[[objects]]
+type = \"triangle\"
+vertices = [[0, 5, 0], [1, 5, 0], [0, 5, 1]]
+material = \"lamp\"
+
+[[objects]]
+type = \"sphere\"
+center = [0, 0, -3]
+radius = 1
+material = \"red\"
+
+[[objects]]
+type = \"instance\"
+shape = \"lamp_post\"
+translate = [0, 0, -10]
+",
+            CAMERA
+        );
+
+        let scene = parse(&content).unwrap();
+        let lights = scene.lights.into_objects();
+
+        assert_eq!(3, scene.world.into_objects().len());
+        assert_eq!(2, lights.len());
+        let lamp = lights[1].bounding_box().unwrap();
+        assert_eq!(Vector3::from((0.5, 2.5, -9.5)), lamp.max);
+    }
+
+    #[test]
+    fn reports_empty_shape() {
+        let content = format!("{}\n[shapes]\nnothing = []\n", CAMERA);
+
+        let error = parse(&content).err().unwrap();
+
+        assert_eq!("line 8: empty shape 'nothing'", error.to_string());
+    }
+
+    #[test]
+    fn reports_unknown_shape() {
+        let content = format!(
+            "{}
+[[objects]]
+type = \"instance\"
+shape = \"ball\"
+",
+            CAMERA
+        );
+
+        let error = parse(&content).err().unwrap();
+
+        assert_eq!("line 7: unknown shape 'ball'", error.to_string());
+    }
+
+    #[test]
+    fn reports_null_scale() {
+        let content = format!(
+            "{}
+[materials.red]
+type = \"lambertian\"
+albedo = [1, 0, 0]
+
+[[shapes.ball]]
+type = \"sphere\"
+center = [0, 0, 0]
+radius = 1
+material = \"red\"
+
+[[objects]]
+type = \"instance\"
+shape = \"ball\"
+scale = 0
+",
+            CAMERA
+        );
+
+        let error = parse(&content).err().unwrap();
+
+        assert_eq!("line 17: scale factors must not be null", error.to_string());
+    }
+
+    #[test]
+    fn reports_missing_camera() {
+        let error = parse("").err().unwrap();
+
+        assert_eq!("line 1: missing field `camera`", error.to_string());
+    }
+
+    #[test]
+    fn reports_line_of_invalid_object() {
+        let content = format!(
+            "{}
+[[objects]]
+type = \"sphere\"
+center = [0, 0]
+radius = 1
+material = \"red\"
+",
+            CAMERA
+        );
+
+        let error = parse(&content).err().unwrap();
+
+        assert_eq!(
+            "line 7: invalid length 2, expected an array of length 3",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn reports_line_of_unknown_material() {
+        let content = format!(
+            "{}
+[[objects]]
+type = \"sphere\"
+center = [0, 0, 0]
+radius = 1
+material = \"red\"
+",
+            CAMERA
+        );
+
+        let error = parse(&content).err().unwrap();
+
+        assert_eq!("line 7: unknown material 'red'", error.to_string());
+    }
+
+    #[test]
+    fn reports_unknown_object_type() {
+        let content = format!("{}\n[[objects]]\ntype = \"cube\"\n", CAMERA);
+
+        let error = parse(&content).err().unwrap();
+
+        assert!(error
+            .to_string()
+            .starts_with("line 8: unknown variant `cube`"));
+    }
+}
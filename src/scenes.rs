@@ -1,41 +1,78 @@
 use crate::camera::Camera;
-use crate::hit::{Hittable, HittableList, Sphere};
-use crate::material::{Dielectric, Lambertian, Metal};
+use crate::hit::{BoxShape, FlipNormals, Hittable, HittableList, Sphere, XYRect, XZRect, YZRect};
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::medium::Atmosphere;
+use crate::radiance::Radiance;
+use crate::ray::Ray;
+use crate::texture::ConstantTexture;
 use crate::vector3::Vector3;
 use rand::Rng;
+use std::sync::Arc;
 
-pub fn get_scene_1((width, height): (u32, u32)) -> (HittableList, Camera) {
+/// What a ray sees when it leaves the scene without hitting anything.
+pub enum Background {
+    Sky,
+    Uniform(Radiance),
+}
+
+impl Background {
+    pub fn radiance(&self, ray: &Ray) -> Radiance {
+        match self {
+            Background::Sky => {
+                let normalized_direction = ray.point_at_parameter(1.).normalized();
+                let t = 0.5 * (normalized_direction.y + 1.);
+
+                let white = Radiance::new(1., 1., 1.);
+                let blue = Radiance::new(0.5, 0.7, 1.);
+
+                white.lerp(blue, t)
+            }
+            Background::Uniform(radiance) => *radiance,
+        }
+    }
+}
+
+pub struct Scene {
+    pub world: HittableList,
+    /// Emitting objects, also part of `world`, which are sampled explicitly.
+    pub lights: HittableList,
+    pub camera: Camera,
+    pub background: Background,
+    pub atmosphere: Option<Atmosphere>,
+}
+
+pub fn get_scene_1((width, height): (u32, u32)) -> Scene {
     let sphere_1 = Box::new(Sphere::new(
         Vector3::from((0., 0., -1.)),
         0.5,
-        Box::new(Lambertian {
-            albedo: Vector3::from((0.2, 1., 0.2)),
-        }),
+        Box::new(Lambertian::new(ConstantTexture::new(Vector3::from((
+            0.2, 1., 0.2,
+        ))))),
     ));
     let sphere_2 = Box::new(Sphere::new(
         Vector3::from((0., -100.5, -1.)),
         100.,
-        Box::new(Lambertian {
-            albedo: Vector3::from((0.5, 0.5, 0.5)),
-        }),
+        Box::new(Lambertian::new(ConstantTexture::new(Vector3::from((
+            0.5, 0.5, 0.5,
+        ))))),
     ));
 
     let sphere_3 = Box::new(Sphere::new(
         Vector3::from((1.3, 0., -1.)),
         0.5,
-        Box::new(Metal {
-            albedo: Vector3::from((0.8, 0.6, 0.2)),
-            fuzziness: 0.3,
-        }),
+        Box::new(Metal::new(
+            ConstantTexture::new(Vector3::from((0.8, 0.6, 0.2))),
+            0.3,
+        )),
     ));
 
     let sphere_4 = Box::new(Sphere::new(
         Vector3::from((-1.3, 0., -1.)),
         0.5,
-        Box::new(Metal {
-            albedo: Vector3::from((0.8, 0.8, 0.2)),
-            fuzziness: 1.,
-        }),
+        Box::new(Metal::new(
+            ConstantTexture::new(Vector3::from((0.8, 0.8, 0.2))),
+            1.,
+        )),
     ));
 
     let sphere_5 = Box::new(Sphere::new(
@@ -75,25 +112,30 @@ pub fn get_scene_1((width, height): (u32, u32)) -> (HittableList, Camera) {
         focus_distance,
     );
 
-    (world, camera)
+    Scene {
+        world,
+        lights: HittableList::new(Vec::new()),
+        camera,
+        background: Background::Sky,
+        atmosphere: None,
+    }
 }
 
-pub fn get_scene_2((width, height): (u32, u32)) -> (HittableList, Camera) {
+pub fn get_scene_2<R: Rng>((width, height): (u32, u32), rng: &mut R) -> Scene {
     let mut spheres: Vec<Box<dyn Hittable>> = Vec::new();
 
     let ground_sphere = Box::new(Sphere::new(
         Vector3::from((0., -1000., 0.)),
         1000.,
-        Box::new(Lambertian {
-            albedo: Vector3::from((0.5, 0.5, 0.5)),
-        }),
+        Box::new(Lambertian::new(ConstantTexture::new(Vector3::from((
+            0.5, 0.5, 0.5,
+        ))))),
     ));
 
     spheres.push(ground_sphere);
 
     let center = Vector3::from((4., 0.2, 0.));
 
-    let mut rng = rand::thread_rng();
     for a in -11..11 {
         for b in -11..11 {
             let material_choice = rng.gen_range(0., 1.);
@@ -111,26 +153,24 @@ pub fn get_scene_2((width, height): (u32, u32)) -> (HittableList, Camera) {
                     Box::new(Sphere::new(
                         position,
                         0.2,
-                        Box::new(Lambertian {
-                            albedo: Vector3::from((
-                                rng.gen_range(0., 1.) * rng.gen_range(0., 1.),
-                                rng.gen_range(0., 1.) * rng.gen_range(0., 1.),
-                                rng.gen_range(0., 1.) * rng.gen_range(0., 1.),
-                            )),
-                        }),
+                        Box::new(Lambertian::new(ConstantTexture::new(Vector3::from((
+                            rng.gen_range(0., 1.) * rng.gen_range(0., 1.),
+                            rng.gen_range(0., 1.) * rng.gen_range(0., 1.),
+                            rng.gen_range(0., 1.) * rng.gen_range(0., 1.),
+                        ))))),
                     ))
                 } else if material_choice < 0.95 {
                     Box::new(Sphere::new(
                         position,
                         0.2,
-                        Box::new(Metal {
-                            albedo: Vector3::from((
+                        Box::new(Metal::new(
+                            ConstantTexture::new(Vector3::from((
                                 0.5 * (1. + rng.gen_range(0., 1.)),
                                 0.5 * (1. + rng.gen_range(0., 1.)),
                                 0.5 * (1. + rng.gen_range(0., 1.)),
-                            )),
-                            fuzziness: 0.5 * rng.gen_range(0., 1.),
-                        }),
+                            ))),
+                            0.5 * rng.gen_range(0., 1.),
+                        )),
                     ))
                 } else {
                     Box::new(Sphere::new(
@@ -149,18 +189,18 @@ pub fn get_scene_2((width, height): (u32, u32)) -> (HittableList, Camera) {
     spheres.push(Box::new(Sphere::new(
         Vector3::from((-4., 1., 0.)),
         1.,
-        Box::new(Lambertian {
-            albedo: Vector3::from((0.4, 0.2, 0.1)),
-        }),
+        Box::new(Lambertian::new(ConstantTexture::new(Vector3::from((
+            0.4, 0.2, 0.1,
+        ))))),
     )));
 
     spheres.push(Box::new(Sphere::new(
         Vector3::from((4., 1., 0.)),
         1.,
-        Box::new(Metal {
-            albedo: Vector3::from((0.8, 0.8, 0.2)),
-            fuzziness: 0.,
-        }),
+        Box::new(Metal::new(
+            ConstantTexture::new(Vector3::from((0.8, 0.8, 0.2))),
+            0.,
+        )),
     )));
 
     spheres.push(Box::new(Sphere::new(
@@ -190,5 +230,127 @@ pub fn get_scene_2((width, height): (u32, u32)) -> (HittableList, Camera) {
         focus_distance,
     );
 
-    (world, camera)
+    Scene {
+        world,
+        lights: HittableList::new(Vec::new()),
+        camera,
+        background: Background::Sky,
+        atmosphere: None,
+    }
+}
+
+pub fn get_cornell_box((width, height): (u32, u32)) -> Scene {
+    let red = || -> Box<dyn Material> {
+        Box::new(Lambertian::new(ConstantTexture::new(Vector3::from((
+            0.65, 0.05, 0.05,
+        )))))
+    };
+    let white = || -> Box<dyn Material> {
+        Box::new(Lambertian::new(ConstantTexture::new(Vector3::from((
+            0.73, 0.73, 0.73,
+        )))))
+    };
+    let green = || -> Box<dyn Material> {
+        Box::new(Lambertian::new(ConstantTexture::new(Vector3::from((
+            0.12, 0.45, 0.15,
+        )))))
+    };
+    let light: Arc<dyn Hittable> = Arc::new(FlipNormals::new(Box::new(XZRect::new(
+        (213., 343.),
+        (227., 332.),
+        554.,
+        Box::new(DiffuseLight {
+            emit: Radiance::new(15., 15., 15.),
+        }),
+    ))));
+
+    let objects: Vec<Box<dyn Hittable>> = vec![
+        Box::new(FlipNormals::new(Box::new(YZRect::new(
+            (0., 555.),
+            (0., 555.),
+            555.,
+            green(),
+        )))),
+        Box::new(YZRect::new((0., 555.), (0., 555.), 0., red())),
+        Box::new(light.clone()),
+        Box::new(FlipNormals::new(Box::new(XZRect::new(
+            (0., 555.),
+            (0., 555.),
+            555.,
+            white(),
+        )))),
+        Box::new(XZRect::new((0., 555.), (0., 555.), 0., white())),
+        Box::new(FlipNormals::new(Box::new(XYRect::new(
+            (0., 555.),
+            (0., 555.),
+            555.,
+            white(),
+        )))),
+        Box::new(BoxShape::new(
+            Vector3::from((130., 0., 65.)),
+            Vector3::from((295., 165., 230.)),
+            white(),
+        )),
+        Box::new(BoxShape::new(
+            Vector3::from((265., 0., 295.)),
+            Vector3::from((430., 330., 460.)),
+            white(),
+        )),
+    ];
+
+    let look_from = Vector3::from((278., 278., -800.));
+    let look_at = Vector3::from((278., 278., 0.));
+    let up = Vector3::from((0., 1., 0.));
+
+    let camera = Camera::new(
+        look_from,
+        look_at,
+        up,
+        40.,
+        width as f64 / height as f64,
+        0.,
+        10.,
+    );
+
+    Scene {
+        world: HittableList::new(objects),
+        lights: HittableList::new(vec![Box::new(light)]),
+        camera,
+        background: Background::Uniform(Radiance::default()),
+        atmosphere: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Pcg32;
+
+    #[test]
+    fn sky_is_white_below_and_blue_above() {
+        let down = Ray::new(Vector3::default(), Vector3::from((0., -1., 0.)));
+        let up = Ray::new(Vector3::default(), Vector3::from((0., 2., 0.)));
+
+        assert_eq!(Radiance::new(1., 1., 1.), Background::Sky.radiance(&down));
+        assert_eq!(Radiance::new(0.5, 0.7, 1.), Background::Sky.radiance(&up));
+    }
+
+    #[test]
+    fn uniform_background_ignores_direction() {
+        let background = Background::Uniform(Radiance::default());
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 1., 0.)));
+
+        assert_eq!(Radiance::default(), background.radiance(&ray));
+    }
+
+    #[test]
+    fn cornell_box_fills_the_view() {
+        let mut rng = Pcg32::new(0, 0);
+        let scene = get_cornell_box((100, 100));
+
+        for &(u, v) in &[(0.5, 0.5), (0.1, 0.1), (0.9, 0.9), (0.5, 0.9)] {
+            let ray = scene.camera.get_ray(u, v, &mut rng);
+            assert!(scene.world.hit(&ray, 0.001, f64::MAX).is_some());
+        }
+    }
 }
@@ -0,0 +1,317 @@
+use crate::color::Color;
+use crate::perlin::Perlin;
+use crate::ppm::{self, PpmError};
+use crate::radiance::Radiance;
+use crate::render::Framebuffer;
+use crate::vector3::Vector3;
+use std::path::Path;
+use std::sync::Arc;
+
+const TURBULENCE_DEPTH: u32 = 7;
+
+/// A color varying over a surface, looked up with the surface coordinates of a
+/// hit and its position in space.
+pub trait Texture: Send + Sync {
+    fn value(&self, uv: (f64, f64), point: &Vector3) -> Vector3;
+}
+
+pub struct ConstantTexture {
+    pub color: Vector3,
+}
+
+impl ConstantTexture {
+    pub fn new(color: Vector3) -> Self {
+        ConstantTexture { color }
+    }
+}
+
+impl Texture for ConstantTexture {
+    fn value(&self, _uv: (f64, f64), _point: &Vector3) -> Vector3 {
+        self.color
+    }
+}
+
+/// Alternates between two textures in space, which makes the pattern
+/// independent of the surface parametrization.
+pub struct CheckerTexture {
+    pub odd: Arc<dyn Texture>,
+    pub even: Arc<dyn Texture>,
+    pub frequency: f64,
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, uv: (f64, f64), point: &Vector3) -> Vector3 {
+        let sines = (self.frequency * point.x).sin()
+            * (self.frequency * point.y).sin()
+            * (self.frequency * point.z).sin();
+
+        if sines < 0. {
+            self.odd.value(uv, point)
+        } else {
+            self.even.value(uv, point)
+        }
+    }
+}
+
+fn grey(value: f64) -> Vector3 {
+    let value = value.clamp(0., 1.);
+    Vector3::from((value, value, value))
+}
+
+/// Raw Perlin noise remapped to [0, 1].
+pub struct NoiseTexture {
+    pub noise: Perlin,
+    pub scale: f64,
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _uv: (f64, f64), point: &Vector3) -> Vector3 {
+        grey(0.5 * (1. + self.noise.noise(&(*point * self.scale))))
+    }
+}
+
+/// Veins along Z, with a phase disturbed by turbulence.
+pub struct MarbleTexture {
+    pub noise: Perlin,
+    pub scale: f64,
+    pub turbulence: f64,
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _uv: (f64, f64), point: &Vector3) -> Vector3 {
+        let phase =
+            self.scale * point.z + self.turbulence * self.noise.turbulence(point, TURBULENCE_DEPTH);
+        grey(0.5 * (1. + phase.sin()))
+    }
+}
+
+/// Concentric rings around the Y axis, blending from `light` to `dark`.
+pub struct WoodTexture {
+    pub noise: Perlin,
+    pub scale: f64,
+    pub light: Vector3,
+    pub dark: Vector3,
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _uv: (f64, f64), point: &Vector3) -> Vector3 {
+        let distance = (point.x * point.x + point.z * point.z).sqrt();
+        let rings = self.scale * distance + self.noise.noise(point);
+        let t = rings - rings.floor();
+        self.light * (1. - t) + self.dark * t
+    }
+}
+
+/// Fractal brownian motion remapped to [0, 1].
+pub struct FbmTexture {
+    pub noise: Perlin,
+    pub scale: f64,
+    pub octaves: u32,
+}
+
+impl Texture for FbmTexture {
+    fn value(&self, _uv: (f64, f64), point: &Vector3) -> Vector3 {
+        grey(0.5 * (1. + self.noise.fbm(&(*point * self.scale), self.octaves)))
+    }
+}
+
+/// Maps an image on the surface, `(0, 0)` being its bottom left corner.
+/// Values are kept linear.
+pub struct ImageTexture {
+    image: Arc<Framebuffer<Vector3>>,
+}
+
+fn color_to_linear(color: Color) -> Vector3 {
+    let (r, g, b): (f64, f64, f64) = color.into();
+    let to_linear = |value: f64| (value / 255.).powi(2);
+    Vector3::from((to_linear(r), to_linear(g), to_linear(b)))
+}
+
+fn radiance_to_vector(radiance: Radiance) -> Vector3 {
+    Vector3::from((radiance.r, radiance.g, radiance.b))
+}
+
+fn convert_image<T, F>(image: &Framebuffer<T>, convert: F) -> Framebuffer<Vector3>
+where
+    T: Copy + Default,
+    F: Fn(T) -> Vector3,
+{
+    let mut converted = Framebuffer::new(image.width, image.height);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            converted.set(x, y, convert(image.get(x, y)));
+        }
+    }
+    converted
+}
+
+impl ImageTexture {
+    pub fn new(image: Framebuffer<Vector3>) -> Self {
+        ImageTexture {
+            image: Arc::new(image),
+        }
+    }
+
+    /// Uses an 8 bits image, whose colors are gamma corrected like the renders.
+    pub fn from_colors(image: &Framebuffer<Color>) -> Self {
+        ImageTexture::new(convert_image(image, color_to_linear))
+    }
+
+    /// Loads a PFM image as is, or a PPM one as gamma corrected colors.
+    pub fn load(path: &Path) -> Result<Self, PpmError> {
+        let is_float_map = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("pfm"));
+
+        if is_float_map {
+            let image = ppm::load_pfm(path)?;
+            Ok(ImageTexture::new(convert_image(&image, radiance_to_vector)))
+        } else {
+            Ok(ImageTexture::from_colors(&ppm::load_ppm(path)?))
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, (u, v): (f64, f64), _point: &Vector3) -> Vector3 {
+        let width = self.image.width;
+        let height = self.image.height;
+        let x = ((u.clamp(0., 1.) * width as f64) as u32).min(width - 1);
+        let y = ((v.clamp(0., 1.) * height as f64) as u32).min(height - 1);
+
+        self.image.get(x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn get_perlin() -> Perlin {
+        Perlin::new(&mut StdRng::seed_from_u64(0))
+    }
+
+    fn is_in_unit_range(color: Vector3) -> bool {
+        (0..3).all(|axis| (0. ..=1.).contains(&color[axis]))
+    }
+
+    fn constant(r: f64, g: f64, b: f64) -> Arc<dyn Texture> {
+        Arc::new(ConstantTexture::new(Vector3::from((r, g, b))))
+    }
+
+    #[test]
+    fn constant_texture_ignores_coordinates() {
+        let texture = ConstantTexture::new(Vector3::from((0.1, 0.2, 0.3)));
+
+        assert_eq!(
+            Vector3::from((0.1, 0.2, 0.3)),
+            texture.value((0.7, 0.2), &Vector3::from((5., 1., 2.)))
+        );
+    }
+
+    #[test]
+    fn checker_texture_alternates_in_space() {
+        let texture = CheckerTexture {
+            odd: constant(0., 0., 0.),
+            even: constant(1., 1., 1.),
+            frequency: 1.,
+        };
+        let half_pi = std::f64::consts::FRAC_PI_2;
+
+        assert_eq!(
+            Vector3::from((1., 1., 1.)),
+            texture.value((0., 0.), &Vector3::from((half_pi, half_pi, half_pi)))
+        );
+        assert_eq!(
+            Vector3::from((0., 0., 0.)),
+            texture.value((0., 0.), &Vector3::from((-half_pi, half_pi, half_pi)))
+        );
+    }
+
+    #[test]
+    fn procedural_textures_stay_in_unit_range() {
+        let textures: Vec<Box<dyn Texture>> = vec![
+            Box::new(NoiseTexture {
+                noise: get_perlin(),
+                scale: 4.,
+            }),
+            Box::new(MarbleTexture {
+                noise: get_perlin(),
+                scale: 4.,
+                turbulence: 10.,
+            }),
+            Box::new(WoodTexture {
+                noise: get_perlin(),
+                scale: 8.,
+                light: Vector3::from((0.8, 0.6, 0.4)),
+                dark: Vector3::from((0.4, 0.2, 0.1)),
+            }),
+            Box::new(FbmTexture {
+                noise: get_perlin(),
+                scale: 2.,
+                octaves: 6,
+            }),
+        ];
+
+        for texture in &textures {
+            for i in 0..100 {
+                let t = i as f64 * 0.31;
+                let point = Vector3::from((t, -t * 0.5, t * 1.7));
+                assert!(is_in_unit_range(texture.value((0., 0.), &point)));
+            }
+        }
+    }
+
+    #[test]
+    fn noise_texture_is_mid_grey_on_lattice_points() {
+        let texture = NoiseTexture {
+            noise: get_perlin(),
+            scale: 1.,
+        };
+
+        let value = texture.value((0., 0.), &Vector3::from((2., 3., 4.)));
+
+        assert!((value.x - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn wood_texture_is_light_at_the_center_of_rings() {
+        let light = Vector3::from((0.8, 0.6, 0.4));
+        let texture = WoodTexture {
+            noise: get_perlin(),
+            scale: 8.,
+            light,
+            dark: Vector3::default(),
+        };
+
+        assert_eq!(light, texture.value((0., 0.), &Vector3::default()));
+    }
+
+    #[test]
+    fn image_texture_maps_bottom_left_corner_to_origin() {
+        let mut image = Framebuffer::new(2, 2);
+        image.set(0, 0, Vector3::from((1., 0., 0.)));
+        image.set(1, 1, Vector3::from((0., 0., 1.)));
+        let texture = ImageTexture::new(image);
+        let point = Vector3::default();
+
+        assert_eq!(Vector3::from((1., 0., 0.)), texture.value((0., 0.), &point));
+        assert_eq!(Vector3::from((0., 0., 1.)), texture.value((1., 1.), &point));
+        assert_eq!(Vector3::default(), texture.value((0.9, 0.1), &point));
+    }
+
+    #[test]
+    fn image_texture_linearizes_colors() {
+        let mut image = Framebuffer::new(1, 1);
+        image.set(0, 0, Color::new(255, 0, 51));
+        let texture = ImageTexture::from_colors(&image);
+
+        let value = texture.value((0.5, 0.5), &Vector3::default());
+
+        assert_eq!((1., 0.), (value.x, value.y));
+        assert!((value.z - 0.04).abs() < 1e-9);
+    }
+}
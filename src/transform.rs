@@ -0,0 +1,555 @@
+use crate::aabb::Aabb;
+use crate::hit::{solid_angle_per_area, HitRecord, Hittable, SurfaceSample, SURFACE_OFFSET};
+use crate::ray::Ray;
+use crate::vector3::{get_tangents, Vector3};
+use rand::RngCore;
+use std::f64;
+use std::ops::Mul;
+use std::sync::Arc;
+
+type Degrees = f64;
+
+/// Row major 4x4 matrix acting on column vectors.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Matrix4 {
+    rows: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn from_rows(rows: [[f64; 4]; 4]) -> Self {
+        Matrix4 { rows }
+    }
+
+    pub fn identity() -> Self {
+        let mut rows = [[0.; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            row[i] = 1.;
+        }
+        Matrix4 { rows }
+    }
+
+    pub fn translation(offset: Vector3) -> Self {
+        Matrix4::from_rows([
+            [1., 0., 0., offset.x],
+            [0., 1., 0., offset.y],
+            [0., 0., 1., offset.z],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn scaling(factors: Vector3) -> Self {
+        Matrix4::from_rows([
+            [factors.x, 0., 0., 0.],
+            [0., factors.y, 0., 0.],
+            [0., 0., factors.z, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Counterclockwise rotation around `axis`, when looking down the axis.
+    pub fn rotation(axis: Vector3, angle: Degrees) -> Self {
+        let Vector3 { x, y, z } = axis.normalized();
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let t = 1. - cos;
+
+        Matrix4::from_rows([
+            [
+                cos + x * x * t,
+                x * y * t - z * sin,
+                x * z * t + y * sin,
+                0.,
+            ],
+            [
+                y * x * t + z * sin,
+                cos + y * y * t,
+                y * z * t - x * sin,
+                0.,
+            ],
+            [
+                z * x * t - y * sin,
+                z * y * t + x * sin,
+                cos + z * z * t,
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn transposed(&self) -> Matrix4 {
+        let mut rows = [[0.; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.rows[j][i];
+            }
+        }
+        Matrix4 { rows }
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting, `None` for singular
+    /// matrices.
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut left = self.rows;
+        let mut right = Matrix4::identity().rows;
+
+        for column in 0..4 {
+            let pivot = (column..4).max_by(|&a, &b| {
+                left[a][column]
+                    .abs()
+                    .partial_cmp(&left[b][column].abs())
+                    .unwrap()
+            })?;
+            if left[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            left.swap(column, pivot);
+            right.swap(column, pivot);
+
+            let divisor = left[column][column];
+            for j in 0..4 {
+                left[column][j] /= divisor;
+                right[column][j] /= divisor;
+            }
+
+            for row in 0..4 {
+                if row != column {
+                    let factor = left[row][column];
+                    for j in 0..4 {
+                        left[row][j] -= factor * left[column][j];
+                        right[row][j] -= factor * right[column][j];
+                    }
+                }
+            }
+        }
+
+        Some(Matrix4 { rows: right })
+    }
+
+    fn apply(&self, v: &Vector3, w: f64) -> Vector3 {
+        let row = |i: usize| {
+            self.rows[i][0] * v.x
+                + self.rows[i][1] * v.y
+                + self.rows[i][2] * v.z
+                + self.rows[i][3] * w
+        };
+        Vector3::from((row(0), row(1), row(2)))
+    }
+
+    pub fn transform_point(&self, point: &Vector3) -> Vector3 {
+        self.apply(point, 1.)
+    }
+
+    pub fn transform_vector(&self, vector: &Vector3) -> Vector3 {
+        self.apply(vector, 0.)
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut rows = [[0.; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.rows[i][k] * rhs.rows[k][j]).sum();
+            }
+        }
+        Matrix4 { rows }
+    }
+}
+
+/// An invertible affine transform, keeping its inverse around.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+}
+
+impl Transform {
+    pub fn new(matrix: Matrix4) -> Option<Self> {
+        Some(Transform {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    pub fn identity() -> Self {
+        Transform {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    pub fn translation(offset: Vector3) -> Self {
+        Transform {
+            matrix: Matrix4::translation(offset),
+            inverse: Matrix4::translation(-offset),
+        }
+    }
+
+    pub fn rotation(axis: Vector3, angle: Degrees) -> Self {
+        Transform {
+            matrix: Matrix4::rotation(axis, angle),
+            inverse: Matrix4::rotation(axis, -angle),
+        }
+    }
+
+    /// Panics when a factor is null.
+    pub fn scaling(factors: Vector3) -> Self {
+        assert!(factors.x != 0. && factors.y != 0. && factors.z != 0.);
+
+        Transform {
+            matrix: Matrix4::scaling(factors),
+            inverse: Matrix4::scaling(Vector3::from((
+                1. / factors.x,
+                1. / factors.y,
+                1. / factors.z,
+            ))),
+        }
+    }
+
+    /// Applies `self`, then `next`.
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {
+            matrix: next.matrix * self.matrix,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, point: &Vector3) -> Vector3 {
+        self.matrix.transform_point(point)
+    }
+
+    pub fn vector(&self, vector: &Vector3) -> Vector3 {
+        self.matrix.transform_vector(vector)
+    }
+
+    /// Normals are transformed by the inverse transpose to stay orthogonal to
+    /// the surface. The result is not normalized.
+    pub fn normal(&self, normal: &Vector3) -> Vector3 {
+        self.inverse.transposed().transform_vector(normal)
+    }
+}
+
+/// Places a shared object in the scene. Rays are brought to the object space,
+/// and hits back to the world space.
+pub struct Transformed {
+    object: Arc<dyn Hittable>,
+    transform: Transform,
+    bounding_box: Option<Aabb>,
+}
+
+fn transform_box(transform: &Transform, object_box: &Aabb) -> Aabb {
+    let corner = |index: usize| {
+        let select = |axis: usize| {
+            if index & (1 << axis) == 0 {
+                object_box.min[axis]
+            } else {
+                object_box.max[axis]
+            }
+        };
+        transform.point(&Vector3::from((select(0), select(1), select(2))))
+    };
+
+    let first = corner(0);
+    (1..8)
+        .map(corner)
+        .fold(Aabb::new(first, first), |surrounding, point| {
+            Aabb::new(surrounding.min.min(&point), surrounding.max.max(&point))
+        })
+}
+
+impl Transformed {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Self {
+        let bounding_box = object
+            .bounding_box()
+            .map(|object_box| transform_box(&transform, &object_box));
+
+        Transformed {
+            object,
+            transform,
+            bounding_box,
+        }
+    }
+
+    /// Brings a sample of the object seen from `object_origin` to the world
+    /// space, scaling its density by the ratio of the solid angles covered by
+    /// an element of the surface in both spaces.
+    fn to_world_sample(
+        &self,
+        object_origin: &Vector3,
+        object_sample: &SurfaceSample,
+        origin: &Vector3,
+    ) -> Option<SurfaceSample> {
+        let point = self.transform.point(&object_sample.point);
+        let normal = self.transform.normal(&object_sample.normal).normalized();
+        let (tangent, bitangent) = get_tangents(&object_sample.normal.normalized());
+        let area_scale = self
+            .transform
+            .vector(&tangent)
+            .cross(&self.transform.vector(&bitangent))
+            .norm();
+
+        let solid_angle = solid_angle_per_area(origin, &point, &normal) * area_scale;
+        if solid_angle > 0. {
+            let object_solid_angle =
+                solid_angle_per_area(object_origin, &object_sample.point, &object_sample.normal);
+            Some(SurfaceSample {
+                point,
+                normal,
+                pdf: object_sample.pdf * object_solid_angle / solid_angle,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn to_object_ray(&self, ray: &Ray) -> Ray {
+        let to_object = self.transform.inverse();
+        Ray::with_time(
+            to_object.point(&ray.origin),
+            to_object.vector(&ray.direction),
+            ray.time,
+        )
+    }
+
+    fn to_world_hit<'a>(&self, hit: HitRecord<'a>) -> HitRecord<'a> {
+        HitRecord {
+            point: self.transform.point(&hit.point),
+            normal: self.transform.normal(&hit.normal).normalized(),
+            ..hit
+        }
+    }
+}
+
+impl Hittable for Transformed {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let hit = self.object.hit(&self.to_object_ray(ray), t_min, t_max)?;
+        Some(self.to_world_hit(hit))
+    }
+
+    fn sample_hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        let hit = self
+            .object
+            .sample_hit(&self.to_object_ray(ray), t_min, t_max, rng)?;
+        Some(self.to_world_hit(hit))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounding_box
+    }
+
+    fn sample_surface(&self, origin: &Vector3, rng: &mut dyn RngCore) -> Option<SurfaceSample> {
+        let object_origin = self.transform.inverse().point(origin);
+        let object_sample = self.object.sample_surface(&object_origin, rng)?;
+
+        self.to_world_sample(&object_origin, &object_sample, origin)
+    }
+
+    fn surface_pdf(&self, origin: &Vector3, direction: &Vector3) -> f64 {
+        let to_object = self.transform.inverse();
+        let object_origin = to_object.point(origin);
+        let object_direction = to_object.vector(direction);
+        let object_ray = Ray::new(object_origin, object_direction);
+
+        match self.object.hit(&object_ray, SURFACE_OFFSET, f64::MAX) {
+            Some(hit) => {
+                let object_sample = SurfaceSample {
+                    point: hit.point,
+                    normal: hit.normal,
+                    pdf: self.object.surface_pdf(&object_origin, &object_direction),
+                };
+                self.to_world_sample(&object_origin, &object_sample, origin)
+                    .map_or(0., |sample| sample.pdf)
+            }
+            None => 0.,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hit::{Sphere, XZRect};
+    use crate::material::{Lambertian, Material};
+    use crate::random::Pcg32;
+    use crate::texture::ConstantTexture;
+
+    fn assert_near(expected: Vector3, actual: Vector3) {
+        assert!(
+            (expected - actual).norm() < 1e-9,
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    fn get_unit_sphere() -> Arc<dyn Hittable> {
+        Arc::new(Sphere::new(
+            Vector3::default(),
+            1.,
+            Box::new(Lambertian::new(ConstantTexture::new(Vector3::default()))),
+        ))
+    }
+
+    #[test]
+    fn inverts_matrices() {
+        let matrix = Matrix4::rotation(Vector3::from((1., 2., 3.)), 30.)
+            * Matrix4::translation(Vector3::from((1., -2., 5.)))
+            * Matrix4::scaling(Vector3::from((2., 3., 0.5)));
+
+        let product = matrix * matrix.inverse().unwrap();
+
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1. } else { 0. };
+                assert!((product.rows[i][j] - expected).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn does_not_invert_singular_matrices() {
+        assert!(Matrix4::scaling(Vector3::from((1., 0., 1.)))
+            .inverse()
+            .is_none());
+    }
+
+    #[test]
+    fn rotates_counterclockwise() {
+        let rotation = Transform::rotation(Vector3::from((0., 0., 1.)), 90.);
+
+        assert_near(
+            Vector3::from((0., 1., 0.)),
+            rotation.point(&Vector3::from((1., 0., 0.))),
+        );
+    }
+
+    #[test]
+    fn translates_points_but_not_vectors() {
+        let translation = Transform::translation(Vector3::from((1., 2., 3.)));
+        let v = Vector3::from((1., 1., 1.));
+
+        assert_eq!(Vector3::from((2., 3., 4.)), translation.point(&v));
+        assert_eq!(v, translation.vector(&v));
+    }
+
+    #[test]
+    fn composes_transforms_in_order() {
+        let transform = Transform::scaling(Vector3::from((2., 2., 2.)))
+            .then(&Transform::translation(Vector3::from((1., 0., 0.))));
+        let point = Vector3::from((1., 1., 1.));
+
+        assert_eq!(Vector3::from((3., 2., 2.)), transform.point(&point));
+        assert_near(point, transform.inverse().point(&transform.point(&point)));
+    }
+
+    #[test]
+    fn keeps_normals_orthogonal_to_scaled_surfaces() {
+        let scaling = Transform::scaling(Vector3::from((2., 1., 1.)));
+        let tangent = scaling.vector(&Vector3::from((1., -1., 0.)));
+
+        let normal = scaling.normal(&Vector3::from((1., 1., 0.)));
+
+        assert!(tangent.dot(&normal).abs() < 1e-12);
+    }
+
+    #[test]
+    fn hits_translated_object() {
+        let sphere = Transformed::new(
+            get_unit_sphere(),
+            Transform::translation(Vector3::from((0., 0., -5.))),
+        );
+        let ray = Ray::new(Vector3::default(), Vector3::from((0., 0., -1.)));
+
+        let hit = sphere.hit(&ray, 0., 10.).unwrap();
+
+        assert!((hit.t - 4.).abs() < 1e-12);
+        assert_near(Vector3::from((0., 0., -4.)), hit.point);
+        assert_near(Vector3::from((0., 0., 1.)), hit.normal);
+    }
+
+    #[test]
+    fn hits_scaled_object_with_unit_normals() {
+        let ellipsoid = Transformed::new(
+            get_unit_sphere(),
+            Transform::scaling(Vector3::from((1., 1., 3.))),
+        );
+        let ray = Ray::new(Vector3::from((0., 0., 10.)), Vector3::from((0., 0., -1.)));
+
+        let hit = ellipsoid.hit(&ray, 0., 20.).unwrap();
+
+        assert!((hit.t - 7.).abs() < 1e-12);
+        assert_near(Vector3::from((0., 0., 1.)), hit.normal);
+    }
+
+    #[test]
+    fn bounding_box_surrounds_rotated_object() {
+        let sphere = Transformed::new(
+            get_unit_sphere(),
+            Transform::translation(Vector3::from((2., 0., 0.)))
+                .then(&Transform::rotation(Vector3::from((0., 1., 0.)), 90.)),
+        );
+
+        let bounding_box = sphere.bounding_box().unwrap();
+
+        assert_near(Vector3::from((-1., -1., -3.)), bounding_box.min);
+        assert_near(Vector3::from((1., 1., -1.)), bounding_box.max);
+    }
+
+    #[test]
+    fn shares_objects_between_instances() {
+        let sphere = get_unit_sphere();
+        let instances: Vec<Transformed> = (0..3)
+            .map(|i| {
+                Transformed::new(
+                    sphere.clone(),
+                    Transform::translation(Vector3::from((i as f64 * 3., 0., 0.))),
+                )
+            })
+            .collect();
+
+        assert_eq!(4, Arc::strong_count(&sphere));
+        assert_near(
+            Vector3::from((7., 1., 1.)),
+            instances[2].bounding_box().unwrap().max,
+        );
+    }
+
+    #[test]
+    fn scales_sampling_densities_with_the_surface() {
+        let mut rng = Pcg32::new(0, 0);
+        let material = || -> Box<dyn Material> {
+            Box::new(Lambertian::new(ConstantTexture::new(Vector3::default())))
+        };
+        let unit_square: Arc<dyn Hittable> =
+            Arc::new(XZRect::new((-0.5, 0.5), (-0.5, 0.5), 0., material()));
+        let scaled = Transformed::new(
+            unit_square,
+            Transform::scaling(Vector3::from((2., 1., 4.)))
+                .then(&Transform::translation(Vector3::from((0., 3., 0.)))),
+        );
+        let rect = XZRect::new((-1., 1.), (-2., 2.), 3., material());
+        let origin = Vector3::from((0.5, 0., 0.2));
+
+        for _i in 0..20 {
+            let sample = scaled.sample_surface(&origin, &mut rng).unwrap();
+            let direction = sample.point - origin;
+            let expected_pdf = rect.surface_pdf(&origin, &direction);
+            assert!((sample.pdf - expected_pdf).abs() < 1e-9 * expected_pdf);
+            assert!((scaled.surface_pdf(&origin, &direction) - expected_pdf).abs() < 1e-9);
+        }
+    }
+}
@@ -1,5 +1,5 @@
-use rand::Rng;
-use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use rand::{Rng, RngCore};
+use std::ops::{Add, AddAssign, Div, DivAssign, Index, Mul, MulAssign, Neg, Sub, SubAssign};
 
 #[derive(Debug, Copy, Clone, Default)]
 pub struct Vector3 {
@@ -45,6 +45,27 @@ impl Vector3 {
         normalized.normalize();
         normalized
     }
+
+    /// Product of the components of both vectors, one by one.
+    pub fn component_mul(&self, other: &Vector3) -> Vector3 {
+        Vector3::from((self.x * other.x, self.y * other.y, self.z * other.z))
+    }
+
+    pub fn min(&self, other: &Vector3) -> Vector3 {
+        Vector3 {
+            x: self.x.min(other.x),
+            y: self.y.min(other.y),
+            z: self.z.min(other.z),
+        }
+    }
+
+    pub fn max(&self, other: &Vector3) -> Vector3 {
+        Vector3 {
+            x: self.x.max(other.x),
+            y: self.y.max(other.y),
+            z: self.z.max(other.z),
+        }
+    }
 }
 
 impl From<(f64, f64, f64)> for Vector3 {
@@ -149,33 +170,101 @@ impl DivAssign<f64> for Vector3 {
     }
 }
 
+impl Index<usize> for Vector3 {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &Self::Output {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vector3 axis out of range: {}", axis),
+        }
+    }
+}
+
 impl PartialEq for Vector3 {
     fn eq(&self, other: &Self) -> bool {
         self.x == other.x && self.y == other.y && self.z == other.z
     }
 }
 
-pub fn random_in_unit_sphere() -> Vector3 {
+pub fn random_in_unit_sphere(rng: &mut dyn RngCore) -> Vector3 {
     use std::iter;
 
-    let mut rng = rand::thread_rng();
-
     let in_unit_coordinates: (f64, f64, f64) = iter::repeat_with(|| {
         (
-            rng.gen_range(0., 1.),
-            rng.gen_range(0., 1.),
-            rng.gen_range(0., 1.),
+            rng.gen_range(-1., 1.),
+            rng.gen_range(-1., 1.),
+            rng.gen_range(-1., 1.),
         )
     })
-    .skip_while(|(x, y, z)| Vector3::from((*x, *y, *z)).squared_norm() > 1.)
-    .next()
+    .find(|(x, y, z)| Vector3::from((*x, *y, *z)).squared_norm() <= 1.)
     .unwrap();
     Vector3::from(in_unit_coordinates)
 }
 
+/// Point uniformly distributed in the disk of radius 1 of the XY plane.
+pub fn random_in_unit_disk(rng: &mut dyn RngCore) -> Vector3 {
+    let radius = rng.gen_range(0., 1f64).sqrt();
+    let angle = 2. * std::f64::consts::PI * rng.gen_range(0., 1.);
+    Vector3::from((radius * angle.cos(), radius * angle.sin(), 0.))
+}
+
+/// Two unit vectors completing the unit vector `normal` into an orthonormal
+/// basis, without branching on its orientation (Duff et al., 2017).
+pub fn get_tangents(normal: &Vector3) -> (Vector3, Vector3) {
+    let sign = 1f64.copysign(normal.z);
+    let a = -1. / (sign + normal.z);
+    let b = normal.x * normal.y * a;
+    (
+        Vector3::from((
+            1. + sign * normal.x * normal.x * a,
+            sign * b,
+            -sign * normal.x,
+        )),
+        Vector3::from((b, sign + normal.y * normal.y * a, -normal.y)),
+    )
+}
+
+/// Unit vector around the unit vector `axis`, within the cone of angles whose
+/// cosine is above `cos_theta_max`, and with a density proportional to the
+/// cosine of its angle with `axis` if `cosine_weighted`, uniform otherwise.
+fn random_around(
+    axis: &Vector3,
+    cos_theta_max: f64,
+    cosine_weighted: bool,
+    rng: &mut dyn RngCore,
+) -> Vector3 {
+    let phi = 2. * std::f64::consts::PI * rng.gen_range(0., 1.);
+    let random: f64 = rng.gen_range(0., 1.);
+    let cos_theta = if cosine_weighted {
+        (1. - random * (1. - cos_theta_max * cos_theta_max)).sqrt()
+    } else {
+        1. - random * (1. - cos_theta_max)
+    };
+    let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+
+    let (tangent, bitangent) = get_tangents(axis);
+    tangent * (phi.cos() * sin_theta) + bitangent * (phi.sin() * sin_theta) + *axis * cos_theta
+}
+
+/// Unit vector in the hemisphere around the unit vector `normal`, with a
+/// density of `cos θ / π` over solid angle.
+pub fn random_cosine_direction(normal: &Vector3, rng: &mut dyn RngCore) -> Vector3 {
+    random_around(normal, 0., true, rng)
+}
+
+/// Unit vector uniformly distributed in the cone around the unit vector `axis`
+/// whose half angle has the cosine `cos_theta_max`.
+pub fn random_in_cone(axis: &Vector3, cos_theta_max: f64, rng: &mut dyn RngCore) -> Vector3 {
+    random_around(axis, cos_theta_max, false, rng)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::random::Pcg32;
 
     #[test]
     fn can_be_debug_printed() {
@@ -318,11 +407,106 @@ mod tests {
         assert_eq!(v2, v1.normalized());
     }
 
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn can_be_indexed_by_axis() {
+        let v = Vector3::from((1., 2., 3.));
+
+        assert_eq!(1., v[0]);
+        assert_eq!(2., v[1]);
+        assert_eq!(3., v[2]);
+    }
+
+    #[test]
+    fn can_multiply_components() {
+        let v1 = Vector3::from((1., 5., 3.));
+        let v2 = Vector3::from((2., 0.5, -1.));
+
+        assert_eq!(Vector3::from((2., 2.5, -3.)), v1.component_mul(&v2));
+    }
+
+    #[test]
+    fn can_give_component_wise_min_and_max() {
+        let v1 = Vector3::from((1., 5., 3.));
+        let v2 = Vector3::from((2., 4., 3.));
+
+        assert_eq!(Vector3::from((1., 4., 3.)), v1.min(&v2));
+        assert_eq!(Vector3::from((2., 5., 3.)), v1.max(&v2));
+    }
+
     #[test]
     fn test_random_unit_vector() {
+        let mut rng = Pcg32::new(0, 0);
         for _i in 0..100 {
-            let random_unit = random_in_unit_sphere();
+            let random_unit = random_in_unit_sphere(&mut rng);
             assert!(random_unit.squared_norm() <= 1.);
         }
     }
+
+    #[test]
+    fn random_points_in_sphere_fill_all_octants() {
+        let mut rng = Pcg32::new(0, 0);
+        let mut octants = [false; 8];
+        for _i in 0..100 {
+            let point = random_in_unit_sphere(&mut rng);
+            let octant =
+                (point.x < 0.) as usize + 2 * (point.y < 0.) as usize + 4 * (point.z < 0.) as usize;
+            octants[octant] = true;
+        }
+        assert_eq!([true; 8], octants);
+    }
+
+    #[test]
+    fn random_points_in_disk_stay_in_the_plane() {
+        let mut rng = Pcg32::new(0, 0);
+        for _i in 0..100 {
+            let point = random_in_unit_disk(&mut rng);
+            assert!(point.squared_norm() <= 1.);
+            assert_eq!(0., point.z);
+        }
+    }
+
+    #[test]
+    fn tangents_form_an_orthonormal_basis() {
+        for normal in &[
+            Vector3::from((0., 0., 1.)),
+            Vector3::from((0., 0., -1.)),
+            Vector3::from((1., 2., -3.)).normalized(),
+        ] {
+            let (tangent, bitangent) = get_tangents(normal);
+            assert!((tangent.norm() - 1.).abs() < 1e-12);
+            assert!((bitangent.norm() - 1.).abs() < 1e-12);
+            assert!(tangent.dot(normal).abs() < 1e-12);
+            assert!(bitangent.dot(normal).abs() < 1e-12);
+            assert!(tangent.dot(&bitangent).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn cosine_directions_are_in_the_hemisphere_with_mean_cosine_of_two_thirds() {
+        let normal = Vector3::from((1., 1., 0.)).normalized();
+        let count = 10000;
+        let mut rng = Pcg32::new(0, 0);
+
+        let cosine_sum: f64 = (0..count)
+            .map(|_| {
+                let direction = random_cosine_direction(&normal, &mut rng);
+                assert!((direction.norm() - 1.).abs() < 1e-9);
+                assert!(direction.dot(&normal) >= 0.);
+                direction.dot(&normal)
+            })
+            .sum();
+
+        assert!((cosine_sum / count as f64 - 2. / 3.).abs() < 0.02);
+    }
+
+    #[test]
+    fn cone_directions_stay_in_the_cone() {
+        let axis = Vector3::from((0., -1., 0.));
+        let mut rng = Pcg32::new(0, 0);
+
+        for _i in 0..100 {
+            assert!(random_in_cone(&axis, 0.9, &mut rng).dot(&axis) >= 0.9 - 1e-12);
+        }
+    }
 }
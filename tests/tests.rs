@@ -1,4 +1,8 @@
-use weekend_raytracer::{color, ppm};
+use weekend_raytracer::integrator::{Integrator, PathIntegrator};
+use weekend_raytracer::random::Pcg32;
+use weekend_raytracer::render::{self, Framebuffer};
+use weekend_raytracer::scenes::get_cornell_box;
+use weekend_raytracer::{color, ppm, radiance, scene_file};
 
 #[test]
 fn test_ppm_format() {
@@ -6,3 +10,96 @@ fn test_ppm_format() {
     let ppm_string = ppm::get_file_content(1, 1, |_, _| color::Color::new(0, 0, 0));
     assert_eq!(EXPECTED, ppm_string);
 }
+
+#[test]
+fn test_binary_ppm_round_trip() {
+    let pixels = |x: u32, y: u32| color::Color::new(x as u8, y as u8, 7);
+    let content = ppm::get_binary_file_content(4, 3, pixels);
+    let image = ppm::parse_ppm(&content).unwrap();
+    assert_eq!(pixels(3, 2), image.get(3, 2));
+}
+
+#[test]
+fn test_scene_file_loading() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/scene_1.toml");
+    let scene = scene_file::load_scene(&path, (200, 100)).unwrap();
+    assert_eq!(6, scene.world.into_objects().len());
+}
+
+#[test]
+fn test_textured_scene_file_loading() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/textures.toml");
+    let scene = scene_file::load_scene(&path, (200, 100)).unwrap();
+    assert_eq!(4, scene.world.into_objects().len());
+}
+
+#[test]
+fn test_instanced_scene_file_loading() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/instances.toml");
+    let scene = scene_file::load_scene(&path, (200, 100)).unwrap();
+    assert_eq!(5, scene.world.into_objects().len());
+}
+
+#[test]
+fn test_empty_scene_renders_the_background() {
+    let content = "[camera]\nlook_from = [0, 0, 0]\nlook_at = [0, 0, -1]\nvertical_fov = 90\n";
+    let scene = scene_file::parse_scene(content, (4, 4), std::path::Path::new("")).unwrap();
+    assert!(scene.world.is_empty());
+    let integrator = PathIntegrator {
+        max_depth: 10,
+        roulette_depth: 3,
+    };
+
+    let mut rng = Pcg32::new(0, 0);
+    let ray = scene.camera.get_ray(0.5, 0.5, &mut rng);
+    let radiance = integrator.radiance(
+        &ray,
+        &scene.world,
+        &scene.lights,
+        &scene.background,
+        &mut rng,
+    );
+
+    assert_eq!(scene.background.radiance(&ray), radiance);
+}
+
+fn render_cornell_box(seed: u64, thread_count: usize) -> Framebuffer<radiance::Radiance> {
+    let (width, height) = (16, 16);
+    let scene = get_cornell_box((width, height));
+    let integrator = PathIntegrator {
+        max_depth: 10,
+        roulette_depth: 3,
+    };
+
+    render::render(width, height, thread_count, |x, y| {
+        let mut rng = Pcg32::for_pixel(seed, x, y);
+        let ray = scene.camera.get_ray(
+            (x as f64 + 0.5) / width as f64,
+            (y as f64 + 0.5) / height as f64,
+            &mut rng,
+        );
+        integrator.radiance(
+            &ray,
+            &scene.world,
+            &scene.lights,
+            &scene.background,
+            &mut rng,
+        )
+    })
+}
+
+#[test]
+fn test_seeded_renders_do_not_depend_on_threads() {
+    let single_threaded = render_cornell_box(7, 1);
+    let multi_threaded = render_cornell_box(7, 4);
+    let other_seed = render_cornell_box(8, 4);
+
+    let pixels = |image: &Framebuffer<radiance::Radiance>| -> Vec<radiance::Radiance> {
+        (0..16)
+            .flat_map(|y| (0..16).map(move |x| (x, y)))
+            .map(|(x, y)| image.get(x, y))
+            .collect()
+    };
+    assert_eq!(pixels(&single_threaded), pixels(&multi_threaded));
+    assert_ne!(pixels(&single_threaded), pixels(&other_seed));
+}